@@ -0,0 +1,232 @@
+use chrono::Duration;
+use minijinja::context;
+use sqlx::{PgPool, Row};
+
+use crate::{
+    config::Config,
+    html_report::escape_html,
+    models::SummaryPayload,
+    notify::{notify_all, truncate_chars, Notification, NotifyLevel},
+    templates,
+    text_report::render_alert_text,
+    utils::format_local_datetime,
+};
+
+/// Longest message (e.g. error text) included in chat and push alerts
+const ALERT_MESSAGE_MAX_CHARS: usize = 1500;
+
+/// Classifies an event name such as `snapshot error` or `forget warning`.
+/// Returns `None` for events that never alert (success, start, end, ...).
+pub fn event_alert_level(event: &str) -> Option<NotifyLevel> {
+    let event = event.to_lowercase();
+    let is_operation = ["snapshot", "forget", "prune", "check"]
+        .iter()
+        .any(|op| event.contains(op));
+    if !is_operation {
+        return None;
+    }
+
+    if event.contains("error") {
+        Some(NotifyLevel::Error)
+    } else if event.contains("warning") {
+        Some(NotifyLevel::Warning)
+    } else {
+        None
+    }
+}
+
+/// Records an attempt to send the alert identified by `key`.
+///
+/// Returns `Some(suppressed)` if the alert may be sent now, where `suppressed`
+/// is how many were held back since the last one, or `None` if the last alert
+/// for this key was less than `cooldown` ago (the attempt is counted).
+pub async fn claim_alert(
+    pool: &PgPool,
+    key: &str,
+    cooldown: Duration,
+) -> Result<Option<i64>, sqlx::Error> {
+    // 1) Take the slot if the cooldown has passed, reading the previous count
+    let claimed = sqlx::query(
+        r#"
+        WITH prev AS (
+            SELECT suppressed FROM alert_state WHERE alert_key = $1
+        ), claimed AS (
+            INSERT INTO alert_state (alert_key, last_sent_at, suppressed)
+            VALUES ($1, now(), 0)
+            ON CONFLICT (alert_key) DO UPDATE
+              SET last_sent_at = now(),
+                  suppressed   = 0
+              WHERE alert_state.last_sent_at <= now() - make_interval(secs => $2)
+            RETURNING 1
+        )
+        SELECT COALESCE((SELECT suppressed FROM prev), 0)::BIGINT AS suppressed
+        FROM claimed
+        "#,
+    )
+    .bind(key)
+    .bind(cooldown.num_seconds() as f64)
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(row) = claimed {
+        return Ok(Some(row.get("suppressed")));
+    }
+
+    // 2) Still cooling down, count the suppressed alert
+    sqlx::query("UPDATE alert_state SET suppressed = suppressed + 1 WHERE alert_key = $1")
+        .bind(key)
+        .execute(pool)
+        .await?;
+
+    Ok(None)
+}
+
+/// Records that the one-off alert identified by `key` is being sent.
+/// Returns `false` if it was already sent before.
+pub async fn claim_alert_once(pool: &PgPool, key: &str) -> Result<bool, sqlx::Error> {
+    let claimed = sqlx::query(
+        r#"
+        INSERT INTO alert_state (alert_key, last_sent_at, suppressed)
+        VALUES ($1, now(), 0)
+        ON CONFLICT (alert_key) DO NOTHING
+        RETURNING 1
+        "#,
+    )
+    .bind(key)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(claimed.is_some())
+}
+
+/// Sends an immediate alert for an error (or, if enabled, warning) event.
+///
+/// Alerts are rate-limited per repo, plan and level by `ALERT_COOLDOWN_MINUTES`,
+/// so a flapping plan produces one alert per cooldown, with the number of
+/// suppressed alerts noted in the next one. Failures are logged only; they
+/// never affect storing the event.
+pub async fn send_event_alert(cfg: Config, pool: PgPool, payload: SummaryPayload) {
+    // 1) Only alert for the configured levels
+    let level = match event_alert_level(&payload.event) {
+        Some(NotifyLevel::Error)   if cfg.alerts.on_error   => NotifyLevel::Error,
+        Some(NotifyLevel::Warning) if cfg.alerts.on_warning => NotifyLevel::Warning,
+        _ => return,
+    };
+
+    // 2) Rate limit per repo, plan and level
+    let key = format!("event:{}:{}:{}", level.as_str(), payload.repo, payload.plan);
+    let cooldown = Duration::minutes(cfg.alerts.cooldown_minutes);
+    let suppressed = match claim_alert(&pool, &key, cooldown).await {
+        Ok(Some(n)) => n,
+        Ok(None) => {
+            tracing::info!(
+                "Alert for '{}' on {} / {} suppressed by cooldown",
+                payload.event,
+                payload.repo,
+                payload.plan
+            );
+            return;
+        }
+        Err(e) => {
+            // Prefer a possible duplicate alert over a missed one
+            tracing::error!("Alert rate limit lookup failed, sending anyway: {}", e);
+            0
+        }
+    };
+
+    // 3) Render and send
+    let notification = render_event_alert(&cfg, &payload, level, suppressed);
+    match notify_all(&cfg, &notification).await {
+        Ok(delivered) => tracing::info!(
+            "Alert '{}' sent to {} channel(s)",
+            notification.title,
+            delivered
+        ),
+        Err((_, msg)) => tracing::error!("Alert '{}' failed: {}", notification.title, msg),
+    }
+}
+
+/// Builds the HTML and markdown bodies of an event alert.
+fn render_event_alert(
+    cfg: &Config,
+    payload: &SummaryPayload,
+    level: NotifyLevel,
+    suppressed: i64,
+) -> Notification {
+    let emoji = if level == NotifyLevel::Error { "❌" } else { "⚠️" };
+    let title = format!("{} {}: {} / {}", emoji, payload.event, payload.repo, payload.plan);
+
+    let error = payload
+        .error
+        .as_deref()
+        .or_else(|| payload.snapshot_stats.as_ref().and_then(|s| s.error.as_deref()))
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or("No error message was provided.");
+    let note = if suppressed > 0 {
+        format!("{} similar alert(s) were suppressed since the previous one.", suppressed)
+    } else {
+        String::new()
+    };
+
+    render_alert(
+        cfg,
+        title,
+        level,
+        &[
+            ("Repo",     payload.repo.clone()),
+            ("Plan",     payload.plan.clone()),
+            ("Event",    payload.event.clone()),
+            ("Time",     format_local_datetime(payload.time)),
+            ("Snapshot", payload.snapshot.clone()),
+        ],
+        Some(error.trim()),
+        &note,
+    )
+}
+
+/// Renders an alert for every channel: the `alert_email.html` template and a
+/// plain-text version for email, and a markdown list for chat and push channels.
+///
+/// * `details` - label/value pairs shown as a table
+/// * `message` - optional free text (e.g. an error), shown preformatted
+/// * `note`    - optional closing remark; empty for none
+pub fn render_alert(
+    cfg: &Config,
+    title: String,
+    level: NotifyLevel,
+    details: &[(&str, String)],
+    message: Option<&str>,
+    note: &str,
+) -> Notification {
+    let backrest_url = cfg.backrest_url.clone().unwrap_or_default();
+
+    // Markdown for chat and push channels, with long messages cut short
+    let mut markdown = String::new();
+    for (label, value) in details {
+        markdown.push_str(&format!("**{}:** {}\n", label, value));
+    }
+    if let Some(msg) = message {
+        markdown.push_str(&format!("\n```\n{}\n```\n", truncate_chars(msg, ALERT_MESSAGE_MAX_CHARS)));
+    }
+    if !note.is_empty() {
+        markdown.push_str(&format!("\n_{}_\n", note));
+    }
+    if !backrest_url.is_empty() {
+        markdown.push_str(&format!("\n[Open Backrest]({})\n", backrest_url));
+    }
+
+    // HTML for email, falling back to the markdown text if the template fails
+    let html = templates::render(
+        cfg,
+        "alert_email.html",
+        context! { title, level => level.as_str(), details, message, note },
+    )
+    .unwrap_or_else(|err| {
+        tracing::error!("Failed to render alert_email.html: {:#}", err);
+        format!("<pre>{}</pre>", escape_html(&markdown))
+    });
+
+    let text = render_alert_text(cfg, &title, details, message, note);
+
+    Notification { title, html, text, markdown, level }
+}
@@ -0,0 +1,223 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    fail,
+    config::Config,
+    models::{DailyActivity, ReportTrends, StorageDay, StorageTrend},
+};
+
+/// Number of days shown in the report charts
+pub const TREND_DAYS: i64 = 30;
+
+/// Loads the per-day history charted in the report: the used space of each
+/// mount in `locations`, and the data added and duration of snapshots, for the
+/// `TREND_DAYS` days up to `end`.
+///
+/// Days are bucketed in Postgres in the configured `TZ`, so each day keeps its
+/// own UTC offset across DST changes.
+pub async fn load_report_trends(
+    cfg: &Config,
+    pool: &PgPool,
+    end: DateTime<Utc>,
+    locations: &[String],
+) -> Result<ReportTrends, (StatusCode, &'static str)> {
+    let start = end - Duration::days(TREND_DAYS - 1);
+
+    // 1) Last storage sample of each day, per mount
+    let rows: Vec<(String, NaiveDate, i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT ON (storage_location, day)
+               storage_location,
+               (time_added AT TIME ZONE $3)::date AS day,
+               storage_used_bytes,
+               storage_total_bytes
+          FROM storage
+         WHERE time_added <= $2
+           AND (time_added AT TIME ZONE $3)::date >= ($1 AT TIME ZONE $3)::date
+           AND storage_location = ANY($4)
+         ORDER BY storage_location, day, time_added DESC
+        "#,
+    )
+    .bind(start)
+    .bind(end)
+    .bind(&cfg.timezone)
+    .bind(locations)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| fail!(cfg, "DB error", "storage trend query failed: {}", e))?;
+
+    let mut storage: Vec<StorageTrend> = locations
+        .iter()
+        .map(|l| StorageTrend { location: l.clone(), days: Vec::new() })
+        .collect();
+    for (location, day, used_bytes, total_bytes) in rows {
+        if let Some(trend) = storage.iter_mut().find(|t| t.location == location) {
+            trend.days.push(StorageDay { day, used_bytes, total_bytes });
+        }
+    }
+
+    // 2) Snapshot activity per day, including days without any
+    let daily = sqlx::query_as::<_, DailyActivity>(
+        r#"
+        SELECT d.day,
+               COUNT(ss.summary_id)::BIGINT                    AS snapshots,
+               COALESCE(SUM(ss.data_added), 0)::BIGINT         AS data_added,
+               COALESCE(SUM(ss.total_duration), 0)::BIGINT     AS total_duration
+          FROM (
+                SELECT generate_series(
+                           ($1 AT TIME ZONE $3)::date,
+                           ($2 AT TIME ZONE $3)::date,
+                           INTERVAL '1 day'
+                       )::date AS day
+               ) d
+          LEFT JOIN summaries s
+                 ON (s.time AT TIME ZONE $3)::date = d.day
+                AND s.time <= $2
+          LEFT JOIN snapshot_stats ss ON ss.summary_id = s.id
+         GROUP BY d.day
+         ORDER BY d.day
+        "#,
+    )
+    .bind(start)
+    .bind(end)
+    .bind(&cfg.timezone)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| fail!(cfg, "DB error", "daily activity query failed: {}", e))?;
+
+    Ok(ReportTrends { storage, daily })
+}
+
+//
+// SVG RENDERING
+//
+
+const WIDTH:  f64 = 560.0;
+const HEIGHT: f64 = 120.0;
+/// Plot area, leaving room for the labels above and below
+const PLOT_TOP:    f64 = 18.0;
+const PLOT_BOTTOM: f64 = 102.0;
+const PLOT_LEFT:   f64 = 4.0;
+const PLOT_RIGHT:  f64 = WIDTH - 4.0;
+
+const LABEL_STYLE: &str = r##"font-family="Arial,sans-serif" font-size="10" fill="#606060""##;
+
+/// Line chart of one value per day, scaled between the lowest and highest
+/// value so small changes stay visible. Labelled with the highest and lowest
+/// value and the first and last day.
+pub fn line_chart(points: &[(NaiveDate, f64)], format: fn(f64) -> String, color: &str) -> String {
+    if points.is_empty() {
+        return String::new();
+    }
+
+    // 1) Value range, padded so a flat line sits mid-chart
+    let (lo, hi) = points
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(lo, hi), &(_, v)| (lo.min(v), hi.max(v)));
+    let pad = if hi > lo { (hi - lo) * 0.1 } else { hi.abs().max(1.0) * 0.1 };
+    let (y_min, y_max) = (lo - pad, hi + pad);
+
+    // 2) Points placed by date, so days without a sample keep their spacing
+    let first_day = points[0].0;
+    let span = (points[points.len() - 1].0 - first_day).num_days();
+    let step = if span > 0 { (PLOT_RIGHT - PLOT_LEFT) / span as f64 } else { 0.0 };
+    let coords: Vec<(f64, f64)> = points
+        .iter()
+        .map(|&(day, v)| {
+            let x = PLOT_LEFT + step * (day - first_day).num_days() as f64;
+            let y = PLOT_BOTTOM - (v - y_min) / (y_max - y_min) * (PLOT_BOTTOM - PLOT_TOP);
+            (x, y)
+        })
+        .collect();
+    let line = coords
+        .iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (first_x, last_x) = (coords[0].0, coords[coords.len() - 1].0);
+
+    let mut body = format!(
+        r#"<polygon points="{:.1},{:.1} {} {:.1},{:.1}" fill="{}" fill-opacity="0.15"/>"#,
+        first_x, PLOT_BOTTOM, line, last_x, PLOT_BOTTOM, color
+    );
+    body.push_str(&format!(
+        r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="2" stroke-linejoin="round"/>"#,
+        line, color
+    ));
+    if let Some(&(x, y)) = coords.last() {
+        body.push_str(&format!(r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"/>"#, x, y, color));
+    }
+
+    svg(
+        points,
+        &format!("High {}", format(hi)),
+        &format!("Low {}", format(lo)),
+        &body,
+    )
+}
+
+/// Bar chart of one value per day, scaled from zero. Labelled with the highest
+/// value, the total, and the first and last day.
+pub fn bar_chart(points: &[(NaiveDate, f64)], format: fn(f64) -> String, color: &str) -> String {
+    if points.is_empty() {
+        return String::new();
+    }
+
+    let max = points.iter().map(|&(_, v)| v).fold(0.0, f64::max);
+    let total: f64 = points.iter().map(|&(_, v)| v.max(0.0)).sum();
+
+    // One slot per day, the bar filling most of it
+    let slot = (PLOT_RIGHT - PLOT_LEFT) / points.len() as f64;
+    let mut body = String::new();
+    for (i, &(_, v)) in points.iter().enumerate() {
+        let h = if max > 0.0 { v.max(0.0) / max * (PLOT_BOTTOM - PLOT_TOP) } else { 0.0 };
+        body.push_str(&format!(
+            r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+            PLOT_LEFT + slot * i as f64 + slot * 0.15,
+            PLOT_BOTTOM - h,
+            slot * 0.7,
+            h,
+            color
+        ));
+    }
+
+    svg(
+        points,
+        &format!("Max {}", format(max)),
+        &format!("Total {}", format(total)),
+        &body,
+    )
+}
+
+/// Wraps a plot in the shared frame: baseline, value labels on top, and the
+/// first and last day underneath.
+fn svg(points: &[(NaiveDate, f64)], top_left: &str, top_right: &str, body: &str) -> String {
+    let first = points[0].0.format("%b %-d");
+    let last = points[points.len() - 1].0.format("%b %-d");
+
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100%" viewBox="0 0 {w} {h}" role="img" aria-label="{tl}, {tr}, {first} to {last}" style="display:block; max-width:{w}px;">"#,
+            r#"<text x="{l}" y="11" {style}>{tl}</text>"#,
+            r#"<text x="{r}" y="11" text-anchor="end" {style}>{tr}</text>"#,
+            "{body}",
+            r##"<line x1="{l}" y1="{b}" x2="{r}" y2="{b}" stroke="#ccc" stroke-width="1"/>"##,
+            r#"<text x="{l}" y="116" {style}>{first}</text>"#,
+            r#"<text x="{r}" y="116" text-anchor="end" {style}>{last}</text>"#,
+            "</svg>",
+        ),
+        w = WIDTH,
+        h = HEIGHT,
+        l = PLOT_LEFT,
+        r = PLOT_RIGHT,
+        b = PLOT_BOTTOM,
+        style = LABEL_STYLE,
+        tl = top_left,
+        tr = top_right,
+        first = first,
+        last = last,
+        body = body,
+    )
+}
@@ -1,8 +1,8 @@
-use std::{env, net::SocketAddr};
+use std::{env, net::SocketAddr, str::FromStr};
 
 use anyhow::{Context, Result};
-use chrono::Local;
 use dotenv::dotenv;
+use lettre::message::{Mailbox, Mailboxes};
 use crate::{
     warn
 };
@@ -18,35 +18,319 @@ pub struct Config {
     pub auth_key: String,
     /// Address and port to listen on
     pub listen_addr: SocketAddr,
-    /// Timezone of the server (for timestamping)
+    /// IANA time zone the report charts bucket days in
     pub timezone: String,
+    /// Apply pending schema migrations on startup
+    pub auto_migrate: bool,
 
     // --- SMTP settings (optional) ---
     pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_tls: SmtpTlsMode,
+    /// Connection/command timeout in seconds
+    pub smtp_timeout: u64,
     pub smtp_username: Option<String>,
     pub smtp_password: Option<String>,
-    pub email_from: Option<String>,
-    pub email_to: Option<String>,
+    /// Sender, parsed from `EMAIL_FROM`
+    pub email_from: Option<Mailbox>,
+    /// Recipients, parsed from the comma-separated `EMAIL_TO`, `EMAIL_CC` and `EMAIL_BCC`
+    pub email_to: Vec<Mailbox>,
+    pub email_cc: Vec<Mailbox>,
+    pub email_bcc: Vec<Mailbox>,
     pub send_startup_email: bool,
+    /// Include an SMTP connection test in `/readyz`
+    pub ready_check_smtp: bool,
+
+    // --- Additional notification channels (optional) ---
+    pub notify: NotifyConfig,
+
+    // --- Immediate alerts for failed events ---
+    pub alerts: AlertConfig,
+
+    // --- Expected plan/repo schedules for missed-backup detection ---
+    pub missed: MissedBackupConfig,
 
     // --- Healthcheck settings (optional) ---
     pub healthcheck_url: Option<String>,
 
     // --- Storage mounts to monitor ---
     pub storage_mounts: Vec<StorageConfig>,
+    /// Percent of capacity a mount must drop back below a threshold before it
+    /// counts as cleared, so it doesn't alert on every sample
+    pub storage_hysteresis: f64,
+
+    // --- Storage capacity forecasting ---
+    pub forecast: ForecastConfig,
 
     // --- Misc. variables used for email reports ---
     pub server_name: Option<String>,
     pub backrest_url: Option<String>,
     pub pgadmin_url:  Option<String>,
+    /// Directory of templates that override the built-in ones
+    pub template_dir: Option<String>,
 
     // --- Scheduler settings ---
-    /// Cron expression for when to fire the daily report
-    pub email_frequency: String,
-    /// How many hours back to include in the report window
-    pub stats_interval: i64,
+    /// Scheduled reports; a single `default` one unless `REPORT_NAME_N` is set
+    pub reports: Vec<ReportDefinition>,
+    /// Cron expression for when to sample storage usage
+    pub storage_frequency: String,
     /// Number of retained reports
     pub retained_reports: i64,
+    /// What to do on startup about scheduled reports missed while down
+    pub report_catch_up: ReportCatchUp,
+
+    // --- Data retention settings (optional, unset = keep forever) ---
+    pub retention: RetentionConfig,
+}
+
+/// How long to keep rows in each table, in days
+#[derive(Clone)]
+pub struct RetentionConfig {
+    /// Events in `summaries` (their `snapshot_stats` rows go with them)
+    pub summaries_days: Option<i64>,
+    /// Detailed `snapshot_stats` rows, while keeping the event itself
+    pub snapshot_stats_days: Option<i64>,
+    /// Raw `storage` samples; older ones are downsampled to one per day per mount
+    pub storage_raw_days: Option<i64>,
+    /// Downsampled daily `storage` samples
+    pub storage_daily_days: Option<i64>,
+    /// Scheduled job runs in `job_runs`
+    pub job_runs_days: Option<i64>,
+    /// Cron expression for when to run pruning
+    pub prune_frequency: String,
+}
+
+impl RetentionConfig {
+    /// True if at least one table has a retention limit
+    pub fn is_enabled(&self) -> bool {
+        self.summaries_days.is_some()
+            || self.snapshot_stats_days.is_some()
+            || self.storage_raw_days.is_some()
+            || self.storage_daily_days.is_some()
+            || self.job_runs_days.is_some()
+    }
+}
+
+/// Immediate alerts sent when an event arrives, rather than in the next report
+#[derive(Clone)]
+pub struct AlertConfig {
+    /// Alert on error events
+    pub on_error: bool,
+    /// Alert on warning events
+    pub on_warning: bool,
+    /// Minimum minutes between alerts for the same repo, plan and level
+    pub cooldown_minutes: i64,
+}
+
+/// Projection of when each storage mount fills up
+#[derive(Clone)]
+pub struct ForecastConfig {
+    /// Days of storage samples the growth rate is fitted to
+    pub window_days: i64,
+    /// Percent used that counts as nearly full
+    pub warn_percent: f64,
+    /// Alert when a mount is projected to reach the warning threshold or
+    /// fill up within this many days; 0 disables the alerts
+    pub lead_days: i64,
+}
+
+/// Expected cadences, used to detect plans or repos that stopped running
+#[derive(Clone)]
+pub struct MissedBackupConfig {
+    /// One entry per `EXPECTED_PLAN_N` / `EXPECTED_REPO_N`
+    pub expected: Vec<ExpectedSchedule>,
+    /// Minutes after a scheduled run before it counts as missed
+    pub grace_minutes: i64,
+    /// Cron expression for when to run the check
+    pub check_frequency: String,
+}
+
+/// How often a plan or repo is expected to produce events
+#[derive(Clone)]
+pub struct ExpectedSchedule {
+    /// `"plan"` or `"repo"`
+    pub scope: &'static str,
+    /// Plan or repo ID, as sent by Backrest
+    pub name: String,
+    pub cadence: Cadence,
+}
+
+#[derive(Clone)]
+pub enum Cadence {
+    /// 6-field cron expression matching the Backrest schedule
+    Cron(String),
+    /// Maximum hours allowed between events
+    MaxHours(i64),
+}
+
+/// Non-email notification channels. Each one is enabled by setting its URL.
+#[derive(Clone, Default)]
+pub struct NotifyConfig {
+    /// Generic endpoint that receives a JSON payload
+    pub webhook_url: Option<String>,
+    /// Full ntfy topic URL, e.g. `https://ntfy.sh/my-backups`
+    pub ntfy_url: Option<String>,
+    /// Optional ntfy access token
+    pub ntfy_token: Option<String>,
+    /// Gotify server base URL
+    pub gotify_url: Option<String>,
+    /// Gotify application token
+    pub gotify_token: Option<String>,
+    /// Slack incoming webhook URL
+    pub slack_webhook_url: Option<String>,
+    /// Discord webhook URL
+    pub discord_webhook_url: Option<String>,
+    /// Seconds to wait for a channel's HTTP request before giving up
+    pub timeout: u64,
+}
+
+/// How the SMTP connection is secured
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpTlsMode {
+    /// Plain connection upgraded with STARTTLS (usually port 587)
+    Starttls,
+    /// Implicit TLS from the first byte (usually port 465)
+    Tls,
+    /// No encryption at all (e.g. a local relay on port 25)
+    None,
+}
+
+impl std::str::FromStr for SmtpTlsMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "starttls"              => Ok(SmtpTlsMode::Starttls),
+            "tls" | "ssl"           => Ok(SmtpTlsMode::Tls),
+            "none" | "off" | "plain" => Ok(SmtpTlsMode::None),
+            other => anyhow::bail!("unknown SMTP_TLS mode '{}' (expected starttls, tls or none)", other),
+        }
+    }
+}
+
+/// A named scheduled report
+#[derive(Clone)]
+pub struct ReportDefinition {
+    /// Unique name, used in the API and job history
+    pub name: String,
+    /// Cron expression for when to send it
+    pub frequency: String,
+    /// How many hours back a window covers, unless aligned
+    pub interval_hours: i64,
+    /// How the start of each window is chosen
+    pub window: ReportWindow,
+    pub content: ReportContent,
+}
+
+/// What goes into a report and who receives it
+#[derive(Clone)]
+pub struct ReportContent {
+    /// Template rendering the HTML body
+    pub template: String,
+    /// Replaces `EMAIL_TO` for this report
+    pub email_to: Option<Vec<Mailbox>>,
+    /// Replaces `EMAIL_CC` for this report
+    pub email_cc: Option<Vec<Mailbox>>,
+    /// Replaces `EMAIL_BCC` for this report
+    pub email_bcc: Option<Vec<Mailbox>>,
+    /// Earlier periods shown next to the current window
+    pub compare: ComparePeriods,
+}
+
+impl ReportContent {
+    /// True if the report has recipients of its own, which then replace all of
+    /// `EMAIL_TO`, `EMAIL_CC` and `EMAIL_BCC`
+    pub fn has_recipients(&self) -> bool {
+        self.email_to.is_some() || self.email_cc.is_some() || self.email_bcc.is_some()
+    }
+}
+
+impl Default for ReportContent {
+    fn default() -> Self {
+        ReportContent {
+            template:  "report.html".into(),
+            email_to:  None,
+            email_cc:  None,
+            email_bcc: None,
+            compare:   ComparePeriods::ALL,
+        }
+    }
+}
+
+/// Which earlier periods a report is compared against
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct ComparePeriods {
+    pub day:   bool,
+    pub week:  bool,
+    pub month: bool,
+}
+
+impl ComparePeriods {
+    pub const ALL: ComparePeriods = ComparePeriods { day: true, week: true, month: true };
+}
+
+impl std::str::FromStr for ComparePeriods {
+    type Err = anyhow::Error;
+
+    /// Comma-separated `day`, `week` and `month`, or `none`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut periods = ComparePeriods { day: false, week: false, month: false };
+        for part in s.split(',').map(|p| p.trim().to_lowercase()).filter(|p| !p.is_empty()) {
+            match part.as_str() {
+                "day"   => periods.day = true,
+                "week"  => periods.week = true,
+                "month" => periods.month = true,
+                "none"  => {}
+                other => anyhow::bail!("unknown comparison period '{}' (expected day, week, month or none)", other),
+            }
+        }
+        Ok(periods)
+    }
+}
+
+/// Where a scheduled report's window starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportWindow {
+    /// `STATS_INTERVAL` hours before the report
+    Interval,
+    /// Right after the end of the previous scheduled report
+    Aligned,
+}
+
+impl std::str::FromStr for ReportWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "interval" => Ok(ReportWindow::Interval),
+            "aligned"  => Ok(ReportWindow::Aligned),
+            other => anyhow::bail!("unknown REPORT_WINDOW mode '{}' (expected interval or aligned)", other),
+        }
+    }
+}
+
+/// What to do on startup about scheduled reports missed while the server was down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportCatchUp {
+    /// One report covering everything since the last scheduled report
+    Combined,
+    /// Every missed report, each with its usual window
+    Each,
+    /// Nothing, the missed windows are only logged
+    Skip,
+}
+
+impl std::str::FromStr for ReportCatchUp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "combined" => Ok(ReportCatchUp::Combined),
+            "each"     => Ok(ReportCatchUp::Each),
+            "skip"     => Ok(ReportCatchUp::Skip),
+            other => anyhow::bail!("unknown REPORT_CATCH_UP policy '{}' (expected combined, each or skip)", other),
+        }
+    }
 }
 
 /// One storage mount to track
@@ -54,6 +338,69 @@ pub struct Config {
 pub struct StorageConfig {
     pub path: String,
     pub nickname: Option<String>,
+    /// Usage that counts as nearly full
+    pub warn: Threshold,
+    /// Usage that counts as critically full
+    pub crit: Threshold,
+}
+
+/// A storage usage limit, as percent used or as free space left
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    /// Reached once this percent of the mount is used
+    PercentUsed(f64),
+    /// Reached once no more than this many bytes are free
+    FreeBytes(u64),
+}
+
+impl Threshold {
+    /// Used bytes at which a mount of `total_bytes` reaches the threshold
+    pub fn used_limit(self, total_bytes: u64) -> f64 {
+        match self {
+            Threshold::PercentUsed(pct) => total_bytes as f64 * pct / 100.0,
+            Threshold::FreeBytes(free)  => total_bytes.saturating_sub(free) as f64,
+        }
+    }
+}
+
+impl std::fmt::Display for Threshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Threshold::PercentUsed(pct) => write!(f, "{}% used", pct),
+            Threshold::FreeBytes(free)  => write!(f, "{} free", crate::html_report::format_bytes(*free)),
+        }
+    }
+}
+
+impl std::str::FromStr for Threshold {
+    type Err = anyhow::Error;
+
+    /// `85` or `85%` for percent used; `50GB`, `50 GiB` or `500M` for free
+    /// space (1024-based, as shown in the reports).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let value: f64 = number
+            .parse()
+            .ok()
+            .filter(|v: &f64| *v >= 0.0)
+            .with_context(|| format!("'{}' is not a percent or size", s))?;
+
+        let multiplier = match unit.trim().to_uppercase().as_str() {
+            "" | "%" => {
+                anyhow::ensure!(value <= 100.0, "'{}' is more than 100%", s);
+                return Ok(Threshold::PercentUsed(value));
+            }
+            "B"                => 1u64,
+            "K" | "KB" | "KIB" => 1 << 10,
+            "M" | "MB" | "MIB" => 1 << 20,
+            "G" | "GB" | "GIB" => 1 << 30,
+            "T" | "TB" | "TIB" => 1 << 40,
+            other => anyhow::bail!("unknown unit '{}' in '{}' (expected %, B, KB, MB, GB or TB)", other, s),
+        };
+        Ok(Threshold::FreeBytes((value * multiplier as f64) as u64))
+    }
 }
 
 impl Config {
@@ -78,16 +425,163 @@ impl Config {
             .parse()
             .context("LISTEN_ADDR must be a valid socket address")?;
 
-        // Timezone, default to local
+        // Timezone, default to the OS zone; Postgres needs an IANA name
         let timezone = env::var("TZ")
-            .unwrap_or_else(|_| Local::now().offset().to_string());
+            .ok()
+            .map(|s| s.trim().trim_start_matches(':').to_string())
+            .filter(|s| !s.is_empty())
+            .or_else(|| iana_time_zone::get_timezone().ok())
+            .unwrap_or_else(|| "UTC".into());
+        if timezone.parse::<chrono_tz::Tz>().is_err() {
+            anyhow::bail!("TZ '{}' is not an IANA time zone name (e.g. UTC, America/New_York)", timezone);
+        }
+
+        // Apply pending migrations at startup unless disabled
+        let auto_migrate = env::var("AUTO_MIGRATE")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| {
+                let val = s.to_lowercase();
+                val == "true" || val == "1"
+            })
+            .unwrap_or(true);
 
         // Optional SMTP / email settings
         let smtp_host     = env::var("SMTP_HOST").ok();
+        let smtp_port     = env::var("SMTP_PORT")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().parse::<u16>())
+            .transpose()
+            .context("SMTP_PORT must be a valid port number")?;
+        // TLS mode defaults to implicit TLS on 465 (or no port), STARTTLS otherwise
+        let smtp_tls      = match env::var("SMTP_TLS").ok().filter(|s| !s.trim().is_empty()) {
+            Some(mode) => mode.parse::<SmtpTlsMode>()?,
+            None => match smtp_port {
+                None | Some(465) => SmtpTlsMode::Tls,
+                Some(_)          => SmtpTlsMode::Starttls,
+            },
+        };
+        let smtp_timeout  = env::var("SMTP_TIMEOUT")
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .unwrap_or(30);
         let smtp_username = env::var("SMTP_USERNAME").ok();
         let smtp_password = env::var("SMTP_PASSWORD").ok();
-        let email_from    = env::var("EMAIL_FROM").ok();
-        let email_to      = env::var("EMAIL_TO").ok();
+        // Addresses are parsed once here, so a typo stops startup instead of every send
+        let email_from    = match env::var("EMAIL_FROM").ok().filter(|s| !s.trim().is_empty()) {
+            Some(from) => Some(Mailbox::from_str(from.trim()).map_err(|e| {
+                anyhow::anyhow!("EMAIL_FROM '{}' is not a valid address: {}", from.trim(), e)
+            })?),
+            None => None,
+        };
+        let email_to      = parse_mailboxes("EMAIL_TO", &env::var("EMAIL_TO").unwrap_or_default())?;
+        let email_cc      = parse_mailboxes("EMAIL_CC", &env::var("EMAIL_CC").unwrap_or_default())?;
+        let email_bcc     = parse_mailboxes("EMAIL_BCC", &env::var("EMAIL_BCC").unwrap_or_default())?;
+
+        // Optional notification channels besides email
+        let non_empty = |key: &str| env::var(key).ok().filter(|s| !s.trim().is_empty());
+        let notify = NotifyConfig {
+            webhook_url:         non_empty("WEBHOOK_URL"),
+            ntfy_url:            non_empty("NTFY_URL"),
+            ntfy_token:          non_empty("NTFY_TOKEN"),
+            gotify_url:          non_empty("GOTIFY_URL"),
+            gotify_token:        non_empty("GOTIFY_TOKEN"),
+            slack_webhook_url:   non_empty("SLACK_WEBHOOK_URL"),
+            discord_webhook_url: non_empty("DISCORD_WEBHOOK_URL"),
+            timeout:             non_empty("NOTIFY_TIMEOUT")
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(10),
+        };
+        if notify.gotify_url.is_some() && notify.gotify_token.is_none() {
+            anyhow::bail!("GOTIFY_URL is set but GOTIFY_TOKEN is missing");
+        }
+
+        // Immediate alerts; errors on by default, warnings off, at most one per hour per plan
+        let env_flag = |key: &str, default: bool| {
+            env::var(key)
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .map(|s| {
+                    let val = s.to_lowercase();
+                    val == "true" || val == "1"
+                })
+                .unwrap_or(default)
+        };
+        let alerts = AlertConfig {
+            on_error:         env_flag("ALERT_ON_ERROR", true),
+            on_warning:       env_flag("ALERT_ON_WARNING", false),
+            cooldown_minutes: env::var("ALERT_COOLDOWN_MINUTES")
+                .ok()
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .filter(|&m| m >= 0)
+                .unwrap_or(60),
+        };
+
+        // Expected schedules, e.g. EXPECTED_PLAN_1=nightly with EXPECTED_CRON_1 or EXPECTED_MAX_HOURS_1
+        let mut expected = Vec::new();
+        for idx in 1.. {
+            let plan = env::var(format!("EXPECTED_PLAN_{}", idx)).ok();
+            let repo = env::var(format!("EXPECTED_REPO_{}", idx)).ok();
+            if plan.is_none() && repo.is_none() {
+                break;
+            }
+            // Compose passes unset variables through as empty strings
+            let non_blank = |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+            let (scope, name) = match (non_blank(plan), non_blank(repo)) {
+                (Some(plan), None) => ("plan", plan),
+                (None, Some(repo)) => ("repo", repo),
+                (Some(_), Some(_)) => anyhow::bail!(
+                    "EXPECTED_PLAN_{0} and EXPECTED_REPO_{0} are both set; use one",
+                    idx
+                ),
+                (None, None) => continue,
+            };
+
+            let cron = env::var(format!("EXPECTED_CRON_{}", idx))
+                .ok()
+                .filter(|s| !s.trim().is_empty());
+            let max_hours = env::var(format!("EXPECTED_MAX_HOURS_{}", idx))
+                .ok()
+                .filter(|s| !s.trim().is_empty());
+            let cadence = match (cron, max_hours) {
+                (Some(expr), None) => {
+                    cron::Schedule::from_str(expr.trim()).with_context(|| {
+                        format!("EXPECTED_CRON_{} '{}' is not a valid cron expression", idx, expr)
+                    })?;
+                    Cadence::Cron(expr.trim().to_string())
+                }
+                (None, Some(hours)) => Cadence::MaxHours(
+                    hours
+                        .trim()
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|&h| h > 0)
+                        .with_context(|| {
+                            format!("EXPECTED_MAX_HOURS_{} must be a positive number of hours", idx)
+                        })?,
+                ),
+                _ => anyhow::bail!(
+                    "EXPECTED_{}_{} needs exactly one of EXPECTED_CRON_{1} or EXPECTED_MAX_HOURS_{1}",
+                    scope.to_uppercase(),
+                    idx
+                ),
+            };
+            expected.push(ExpectedSchedule { scope, name, cadence });
+        }
+        let missed = MissedBackupConfig {
+            expected,
+            grace_minutes: env::var("MISSED_GRACE_MINUTES")
+                .ok()
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .filter(|&m| m >= 0)
+                .unwrap_or(60),
+            // Default to every 15 minutes
+            check_frequency: env::var("MISSED_CHECK_FREQUENCY")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or_else(|| "0 */15 * * * *".into()),
+        };
 
         // Optional healthchecks URL for status updates
         let healthcheck_url = env::var("HEALTHCHECK_PING_URL").ok();
@@ -104,6 +598,29 @@ impl Config {
             // If the var wasn't set, default to false
             .unwrap_or(false);
 
+        // READY_CHECK_SMTP, default to leaving SMTP out of /readyz
+        let ready_check_smtp = env::var("READY_CHECK_SMTP")
+            .map(|s| {
+                let val = s.to_lowercase();
+                val == "true" || val == "1"
+            })
+            .unwrap_or(false);
+
+        // Storage usage thresholds, e.g. STORAGE_WARN_THRESHOLD=80% or STORAGE_CRIT_THRESHOLD=20GB (free)
+        let threshold = |key: &str, default: Threshold| -> Result<Threshold> {
+            match env::var(key).ok().filter(|s| !s.trim().is_empty()) {
+                Some(v) => v.parse().with_context(|| format!("{} is invalid", key)),
+                None    => Ok(default),
+            }
+        };
+        let default_warn = threshold("STORAGE_WARN_THRESHOLD", Threshold::PercentUsed(80.0))?;
+        let default_crit = threshold("STORAGE_CRIT_THRESHOLD", Threshold::PercentUsed(90.0))?;
+        let storage_hysteresis = env::var("STORAGE_THRESHOLD_HYSTERESIS")
+            .ok()
+            .and_then(|v| v.trim().trim_end_matches('%').parse::<f64>().ok())
+            .filter(|p| (0.0..100.0).contains(p))
+            .unwrap_or(2.0);
+
         // Optional storage mounts
         let mut storage_mounts = Vec::new();
         for idx in 1.. {
@@ -121,29 +638,189 @@ impl Config {
                 Ok(path) => {
                     let key_nick = format!("STORAGE_NICK_{}", idx);
                     let nickname = env::var(&key_nick).ok().filter(|s| !s.is_empty());
-                    storage_mounts.push(StorageConfig { path, nickname });
+                    let warn = threshold(&format!("STORAGE_WARN_{}", idx), default_warn)?;
+                    let crit = threshold(&format!("STORAGE_CRIT_{}", idx), default_crit)?;
+                    let out_of_order = match (warn, crit) {
+                        (Threshold::PercentUsed(w), Threshold::PercentUsed(c)) => w > c,
+                        (Threshold::FreeBytes(w), Threshold::FreeBytes(c))     => w < c,
+                        _ => false, // depends on the mount's size
+                    };
+                    if out_of_order {
+                        anyhow::bail!(
+                            "the warning threshold of {} ({}) is past its critical threshold ({})",
+                            path, warn, crit
+                        );
+                    }
+                    storage_mounts.push(StorageConfig { path, nickname, warn, crit });
                 }
                 Err(_) => break,
             }
         }
 
+        // Storage forecasting over the last FORECAST_WINDOW_DAYS of samples
+        let forecast = ForecastConfig {
+            window_days:  env::var("FORECAST_WINDOW_DAYS")
+                .ok()
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .filter(|&d| d > 0)
+                .unwrap_or(30),
+            warn_percent: env::var("FORECAST_WARN_PERCENT")
+                .ok()
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .filter(|p| (0.0..=100.0).contains(p))
+                .unwrap_or(90.0),
+            lead_days:    env::var("FORECAST_LEAD_DAYS")
+                .ok()
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .filter(|&d| d >= 0)
+                .unwrap_or(14),
+        };
+
         // Optional misc. variables used for email reports
         let server_name = env::var("SERVER_NAME").ok();
         let backrest_url = env::var("BACKREST_URL").ok();
         let pgadmin_url  = env::var("PGADMIN_URL").ok();
 
+        // Optional directory of template overrides
+        let template_dir = env::var("TEMPLATE_DIR").ok().filter(|s| !s.trim().is_empty());
+        if let Some(dir) = &template_dir {
+            if !std::path::Path::new(dir).is_dir() {
+                anyhow::bail!("TEMPLATE_DIR '{}' is not a directory", dir);
+            }
+        }
+
         // Scheduler settings
         // Default to daily at midnight if you don’t set EMAIL_FREQUENCY
         let email_frequency = env::var("EMAIL_FREQUENCY")
-            .unwrap_or_else(|_| "0 0 * * *".into());
-        // STAT_INTERVAL in hours, default to 24
-        let stats_interval = env::var("STAT_INTERVAL")
-            .map(|v| v.parse::<i64>().unwrap_or(24))
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "0 0 0 * * *".into());
+        // Storage usage is sampled daily at midnight unless STORAGE_FREQUENCY is set
+        let storage_frequency = env::var("STORAGE_FREQUENCY")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "0 0 0 * * *".into());
+        // STATS_INTERVAL in hours, default to 24. STAT_INTERVAL is the old name
+        let stats_interval = env::var("STATS_INTERVAL")
+            .or_else(|_| env::var("STAT_INTERVAL"))
+            .map(|v| v.trim().parse::<i64>().unwrap_or(24))
             .unwrap_or(24);
+        // REPORT_WINDOW, default to the last STATS_INTERVAL hours
+        let report_window = match env::var("REPORT_WINDOW").ok().filter(|s| !s.trim().is_empty()) {
+            Some(mode) => mode.parse::<ReportWindow>()?,
+            None       => ReportWindow::Interval,
+        };
+
+        // Named reports, e.g. REPORT_NAME_1=weekly with REPORT_FREQUENCY_1=0 0 8 * * Mon.
+        // Without any, EMAIL_FREQUENCY and STATS_INTERVAL define a single `default` one
+        let mut reports: Vec<ReportDefinition> = Vec::new();
+        for idx in 1.. {
+            let Ok(name) = env::var(format!("REPORT_NAME_{}", idx)) else {
+                break;
+            };
+            // Compose passes unset variables through as empty strings
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                continue;
+            }
+            if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                anyhow::bail!("REPORT_NAME_{} '{}' may only contain letters, digits, '-' and '_'", idx, name);
+            }
+            if reports.iter().any(|r| r.name == name) {
+                anyhow::bail!("REPORT_NAME_{} '{}' is used by another report", idx, name);
+            }
+            let var = |key: &str| {
+                env::var(format!("REPORT_{}_{}", key, idx))
+                    .ok()
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+            };
+
+            let frequency = var("FREQUENCY")
+                .with_context(|| format!("REPORT_FREQUENCY_{} is required for report '{}'", idx, name))?;
+            cron::Schedule::from_str(&frequency).with_context(|| {
+                format!("REPORT_FREQUENCY_{} '{}' is not a valid cron expression", idx, frequency)
+            })?;
+            let interval_hours = match var("INTERVAL") {
+                Some(hours) => hours
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|&h| h > 0)
+                    .with_context(|| format!("REPORT_INTERVAL_{} must be a positive number of hours", idx))?,
+                None => stats_interval,
+            };
+            let window = match var("WINDOW") {
+                Some(mode) => mode.parse().with_context(|| format!("REPORT_WINDOW_{} is invalid", idx))?,
+                None       => report_window,
+            };
+            let compare = match var("COMPARE") {
+                Some(list) => list.parse().with_context(|| format!("REPORT_COMPARE_{} is invalid", idx))?,
+                None       => ComparePeriods::ALL,
+            };
+            let recipients = |key: &str| {
+                var(key)
+                    .map(|list| parse_mailboxes(&format!("REPORT_{}_{}", key, idx), &list))
+                    .transpose()
+            };
+            let (email_to, email_cc, email_bcc) =
+                (recipients("EMAIL_TO")?, recipients("EMAIL_CC")?, recipients("EMAIL_BCC")?);
+
+            reports.push(ReportDefinition {
+                name,
+                frequency,
+                interval_hours,
+                window,
+                content: ReportContent {
+                    template: var("TEMPLATE").unwrap_or_else(|| "report.html".into()),
+                    email_to,
+                    email_cc,
+                    email_bcc,
+                    compare,
+                },
+            });
+        }
+        if reports.is_empty() {
+            reports.push(ReportDefinition {
+                name:           "default".into(),
+                frequency:      email_frequency,
+                interval_hours: stats_interval,
+                window:         report_window,
+                content:        ReportContent::default(),
+            });
+        }
         // NUM_RETAINED_REPORTS, default to 10
         let retained_reports = env::var("NUM_RETAINED_REPORTS")
             .map(|v| v.parse::<i64>().unwrap_or(10))
             .unwrap_or(10);
+        // REPORT_CATCH_UP, default to one combined report
+        let report_catch_up = match env::var("REPORT_CATCH_UP").ok().filter(|s| !s.trim().is_empty()) {
+            Some(policy) => policy.parse::<ReportCatchUp>()?,
+            None         => ReportCatchUp::Combined,
+        };
+
+        // Data retention, in days. Unset, empty, or non-positive keeps data forever
+        let retention_days = |key: &str| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .filter(|&days| days > 0)
+        };
+        let retention = RetentionConfig {
+            summaries_days:      retention_days("RETENTION_EVENTS_DAYS"),
+            snapshot_stats_days: retention_days("RETENTION_SNAPSHOT_STATS_DAYS"),
+            storage_raw_days:    retention_days("RETENTION_STORAGE_RAW_DAYS"),
+            storage_daily_days:  retention_days("RETENTION_STORAGE_DAILY_DAYS"),
+            // Job history is housekeeping, so it defaults to 90 days rather than forever
+            job_runs_days:       match env::var("RETENTION_JOB_RUNS_DAYS").ok().filter(|s| !s.trim().is_empty()) {
+                Some(_) => retention_days("RETENTION_JOB_RUNS_DAYS"),
+                None    => Some(90),
+            },
+            // Default to daily at 00:30, after the midnight storage update
+            prune_frequency:     env::var("PRUNE_FREQUENCY")
+                .ok()
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or_else(|| "0 30 0 * * *".into()),
+        };
 
         Ok(Config {
             version,
@@ -151,20 +828,74 @@ impl Config {
             auth_key,
             listen_addr,
             timezone,
+            auto_migrate,
             smtp_host,
+            smtp_port,
+            smtp_tls,
+            smtp_timeout,
             smtp_username,
             smtp_password,
             email_from,
             email_to,
+            email_cc,
+            email_bcc,
             send_startup_email,
+            ready_check_smtp,
+            notify,
+            alerts,
+            missed,
             healthcheck_url,
             storage_mounts,
+            storage_hysteresis,
+            forecast,
             server_name,
             backrest_url,
             pgadmin_url,
-            email_frequency,
-            stats_interval,
+            template_dir,
+            reports,
+            storage_frequency,
             retained_reports,
+            report_catch_up,
+            retention,
         })
     }
 }
+
+/// Parses a comma-separated address list such as
+/// `Ops <ops@example.com>, admin@example.com`. A blank value is an empty list.
+fn parse_mailboxes(var: &str, value: &str) -> Result<Vec<Mailbox>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+    Mailboxes::from_str(value)
+        .map(|mbs| mbs.into_iter().collect())
+        .map_err(|e| anyhow::anyhow!("{} '{}' contains an invalid address: {}", var, value, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn threshold_percent() {
+        assert_eq!("85".parse::<Threshold>().unwrap(), Threshold::PercentUsed(85.0));
+        assert_eq!("85%".parse::<Threshold>().unwrap(), Threshold::PercentUsed(85.0));
+        assert_eq!(" 100 % ".parse::<Threshold>().unwrap(), Threshold::PercentUsed(100.0));
+    }
+
+    #[test]
+    fn threshold_free_space() {
+        assert_eq!("50GB".parse::<Threshold>().unwrap(), Threshold::FreeBytes(50 << 30));
+        assert_eq!("50 GiB".parse::<Threshold>().unwrap(), Threshold::FreeBytes(50 << 30));
+        assert_eq!("500M".parse::<Threshold>().unwrap(), Threshold::FreeBytes(500 << 20));
+    }
+
+    #[test]
+    fn threshold_rejects_invalid() {
+        assert!("101%".parse::<Threshold>().is_err());
+        assert!("5XB".parse::<Threshold>().is_err());
+        assert!("GB".parse::<Threshold>().is_err());
+        assert!("-5%".parse::<Threshold>().is_err());
+    }
+}
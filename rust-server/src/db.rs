@@ -1,69 +1,351 @@
-use anyhow::Result;
-use sqlx::{Executor, PgPool};
+use anyhow::{bail, Context, Result};
+use sqlx::{pool::PoolConnection, Acquire, Executor, PgConnection, PgPool, Postgres, Row};
 
-/// Initialize the database connection and ensure required tables exist.
+/// One step of the schema history. Versions must be unique and strictly
+/// increasing; once released, a migration's SQL must never change.
+pub struct Migration {
+    pub version: i64,
+    pub name:    &'static str,
+    pub sql:     &'static str,
+}
+
+/// Ordered list of every schema migration.
+///
+/// Migration 1 uses `IF NOT EXISTS` so deployments created before versioning
+/// existed adopt it without error.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name:    "initial schema",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS summaries (
+              id             SERIAL PRIMARY KEY,
+              created_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
+              task           TEXT NOT NULL,
+              time           TIMESTAMPTZ NOT NULL,
+              event          TEXT NOT NULL,
+              repo           TEXT NOT NULL,
+              plan           TEXT NOT NULL,
+              snapshot       TEXT NOT NULL,
+              error          TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS snapshot_stats (
+              id                    SERIAL PRIMARY KEY,
+              summary_id            INTEGER NOT NULL REFERENCES summaries(id) ON DELETE CASCADE,
+              message_type          TEXT NOT NULL,
+              error                 TEXT,
+              during                TEXT,
+              item                  TEXT,
+              files_new             BIGINT,
+              files_changed         BIGINT,
+              files_unmodified      BIGINT,
+              dirs_new              BIGINT,
+              dirs_changed          BIGINT,
+              dirs_unmodified       BIGINT,
+              data_blobs            BIGINT,
+              tree_blobs            BIGINT,
+              data_added            BIGINT,
+              total_files_processed BIGINT,
+              total_bytes_processed BIGINT,
+              total_duration        DOUBLE PRECISION,
+              snapshot_id           TEXT NOT NULL,
+              percent_done          BIGINT,
+              total_files           BIGINT,
+              files_done            BIGINT,
+              total_bytes           BIGINT,
+              bytes_done            BIGINT,
+              current_files         BIGINT
+            );
+
+            CREATE TABLE IF NOT EXISTS storage (
+              id                     SERIAL PRIMARY KEY,
+              storage_location       TEXT NOT NULL,
+              storage_nickname       TEXT,
+              time_added             TIMESTAMPTZ NOT NULL DEFAULT now(),
+              storage_used_bytes     BIGINT,
+              storage_total_bytes    BIGINT
+            );
+        "#,
+    },
+    Migration {
+        version: 2,
+        name:    "event deduplication",
+        sql: r#"
+            -- Idempotency key supplied by the caller via the `Idempotency-Key` header
+            ALTER TABLE summaries ADD COLUMN IF NOT EXISTS idempotency_key TEXT;
+
+            -- Collapse replayed events stored before deduplication existed,
+            -- keeping the earliest row of each natural key
+            DELETE FROM summaries a
+            USING summaries b
+            WHERE a.id > b.id
+              AND a.repo     = b.repo
+              AND a.plan     = b.plan
+              AND a.snapshot = b.snapshot
+              AND a.event    = b.event
+              AND a.time     = b.time;
+
+            -- Unique constraints used to detect replayed events
+            CREATE UNIQUE INDEX IF NOT EXISTS summaries_natural_key_idx
+              ON summaries (repo, plan, snapshot, event, time);
+            CREATE UNIQUE INDEX IF NOT EXISTS summaries_idempotency_key_idx
+              ON summaries (idempotency_key)
+              WHERE idempotency_key IS NOT NULL;
+        "#,
+    },
+    Migration {
+        version: 3,
+        name:    "time indexes for retention",
+        sql: r#"
+            CREATE INDEX IF NOT EXISTS summaries_time_idx
+              ON summaries (time);
+            CREATE INDEX IF NOT EXISTS storage_location_time_idx
+              ON storage (storage_location, time_added);
+        "#,
+    },
+    Migration {
+        version: 4,
+        name:    "alert rate limiting",
+        sql: r#"
+            -- Last time each alert was sent, and how many were held back since
+            CREATE TABLE IF NOT EXISTS alert_state (
+              alert_key     TEXT PRIMARY KEY,
+              last_sent_at  TIMESTAMPTZ NOT NULL,
+              suppressed    BIGINT NOT NULL DEFAULT 0
+            );
+        "#,
+    },
+    Migration {
+        version: 5,
+        name:    "storage usage levels",
+        sql: r#"
+            -- Usage level each mount last alerted at, for threshold hysteresis
+            CREATE TABLE IF NOT EXISTS storage_usage_state (
+              storage_location  TEXT PRIMARY KEY,
+              level             TEXT NOT NULL,
+              changed_at        TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+        "#,
+    },
+    Migration {
+        version: 6,
+        name:    "job run history",
+        sql: r#"
+            -- One row per scheduled job run; finished_at is NULL while running
+            CREATE TABLE IF NOT EXISTS job_runs (
+              id           BIGSERIAL PRIMARY KEY,
+              job          TEXT NOT NULL,
+              started_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+              finished_at  TIMESTAMPTZ,
+              outcome      TEXT NOT NULL,
+              error        TEXT
+            );
+            CREATE INDEX IF NOT EXISTS job_runs_job_started_idx
+              ON job_runs (job, started_at DESC);
+        "#,
+    },
+    Migration {
+        version: 7,
+        name:    "scheduled report windows",
+        sql: r#"
+            -- Window covered by each report the scheduler sent
+            CREATE TABLE IF NOT EXISTS report_windows (
+              id            BIGSERIAL PRIMARY KEY,
+              window_start  TIMESTAMPTZ NOT NULL,
+              window_end    TIMESTAMPTZ NOT NULL,
+              catch_up      BOOLEAN NOT NULL DEFAULT FALSE,
+              sent_at       TIMESTAMPTZ NOT NULL DEFAULT now()
+            );
+            CREATE INDEX IF NOT EXISTS report_windows_end_idx
+              ON report_windows (window_end);
+        "#,
+    },
+    Migration {
+        version: 8,
+        name:    "named reports",
+        sql: r#"
+            -- Windows are tracked per named report; earlier ones were the default report
+            ALTER TABLE report_windows
+              ADD COLUMN IF NOT EXISTS report TEXT NOT NULL DEFAULT 'default';
+            DROP INDEX IF EXISTS report_windows_end_idx;
+            CREATE INDEX IF NOT EXISTS report_windows_report_end_idx
+              ON report_windows (report, window_end);
+        "#,
+    },
+    Migration {
+        version: 9,
+        name:    "summaries ingestion time index",
+        sql: r#"
+            -- Aligned report windows select events by when they were stored
+            CREATE INDEX IF NOT EXISTS summaries_created_at_idx
+              ON summaries (created_at);
+        "#,
+    },
+    Migration {
+        version: 10,
+        name:    "summaries latest per repo and plan index",
+        sql: r#"
+            -- Metrics read the latest event of each repo and plan
+            CREATE INDEX IF NOT EXISTS summaries_repo_plan_time_idx
+              ON summaries (repo, plan, time DESC);
+        "#,
+    },
+];
+
+/// Arbitrary key for the Postgres advisory lock held while migrating, so two
+/// instances starting at once don't apply the same step twice.
+const MIGRATION_LOCK_KEY: i64 = 0x6261_636b_7265_7374; // "backrest"
+
+/// Arbitrary key for the Postgres advisory lock every ingest transaction holds
+/// shared, so an aligned report can wait for events still being stored.
+const INGEST_LOCK_KEY: i64 = 0x696e_6765_7374; // "ingest"
+
+/// Holds the ingest lock shared until the transaction on `conn` ends. Must be
+/// taken before the event's `created_at` is stamped.
+pub async fn lock_ingest_shared(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock_shared($1)")
+        .bind(INGEST_LOCK_KEY)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Waits until every ingest transaction in flight has committed or rolled back.
+///
+/// Events are stamped with `created_at` before their transaction commits, so a
+/// report selecting by ingestion time could otherwise miss a row that becomes
+/// visible only after the window was sent. Once this returns, every event
+/// stamped so far is visible and later ones are stamped after now.
+pub async fn wait_for_ingests(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(INGEST_LOCK_KEY)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+/// Initialize the database connection and bring the schema up to date.
 /// Returns a configured PgPool.
-pub async fn init_db(database_url: &str) -> Result<PgPool> {
+///
+/// With `auto_migrate` off, pending migrations are an error and must be
+/// applied with the `migrate` command first. A database whose schema is newer
+/// than this binary knows about is always refused.
+pub async fn init_db(database_url: &str, auto_migrate: bool) -> Result<PgPool> {
     // Connect to Postgres
     let pool = PgPool::connect(database_url).await?;
 
-    // Create summaries table with created_at timestamp column
-    pool.execute(r#"
-        CREATE TABLE IF NOT EXISTS summaries (
-          id             SERIAL PRIMARY KEY,
-          created_at     TIMESTAMPTZ NOT NULL DEFAULT now(),
-          task           TEXT NOT NULL,
-          time           TIMESTAMPTZ NOT NULL,
-          event          TEXT NOT NULL,
-          repo           TEXT NOT NULL,
-          plan           TEXT NOT NULL,
-          snapshot       TEXT NOT NULL,
-          error          TEXT
+    let current = schema_version(&pool).await?;
+    let latest  = latest_version();
+
+    if current > latest {
+        bail!(
+            "Database schema is at version {} but this build only knows up to version {}; \
+             refusing to start. Upgrade the application.",
+            current,
+            latest
         );
-    "#).await?;
+    }
+
+    if current < latest {
+        if !auto_migrate {
+            bail!(
+                "Database schema is at version {} but version {} is required; \
+                 run `rust-server migrate` or set AUTO_MIGRATE=true.",
+                current,
+                latest
+            );
+        }
+        migrate(&pool).await?;
+    }
+
+    Ok(pool)
+}
+
+/// Applies every pending migration in order, each in its own transaction.
+/// Returns the versions that were applied.
+pub async fn migrate(pool: &PgPool) -> Result<Vec<i64>> {
+    let mut conn = pool.acquire().await?;
+    ensure_version_table(&mut conn).await?;
 
-    // Create snapshot_stats table
-    pool.execute(r#"
-        CREATE TABLE IF NOT EXISTS snapshot_stats (
-          id                    SERIAL PRIMARY KEY,
-          summary_id            INTEGER NOT NULL REFERENCES summaries(id) ON DELETE CASCADE,
-          message_type          TEXT NOT NULL,
-          error                 TEXT,
-          during                TEXT,
-          item                  TEXT,
-          files_new             BIGINT,
-          files_changed         BIGINT,
-          files_unmodified      BIGINT,
-          dirs_new              BIGINT,
-          dirs_changed          BIGINT,
-          dirs_unmodified       BIGINT,
-          data_blobs            BIGINT,
-          tree_blobs            BIGINT,
-          data_added            BIGINT,
-          total_files_processed BIGINT,
-          total_bytes_processed BIGINT,
-          total_duration        DOUBLE PRECISION,
-          snapshot_id           TEXT NOT NULL,
-          percent_done          BIGINT,
-          total_files           BIGINT,
-          files_done            BIGINT,
-          total_bytes           BIGINT,
-          bytes_done            BIGINT,
-          current_files         BIGINT
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let result = apply_pending(&mut conn).await;
+
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    result
+}
+
+/// Returns the highest applied schema version, or 0 for a database that has
+/// never been migrated.
+pub async fn schema_version(pool: &PgPool) -> Result<i64> {
+    let mut conn = pool.acquire().await?;
+    ensure_version_table(&mut conn).await?;
+
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0)::BIGINT AS version FROM schema_version")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    Ok(row.get("version"))
+}
+
+/// The version the schema will be at once every known migration is applied.
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Creates the bookkeeping table that records applied migrations.
+async fn ensure_version_table(conn: &mut PoolConnection<Postgres>) -> Result<()> {
+    conn.execute(r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+          version     BIGINT PRIMARY KEY,
+          name        TEXT NOT NULL,
+          applied_at  TIMESTAMPTZ NOT NULL DEFAULT now()
         );
     "#).await?;
 
-    // Create storage statistics table
-    pool.execute(r#"
-        CREATE TABLE IF NOT EXISTS storage (
-          id                     SERIAL PRIMARY KEY,
-          storage_location       TEXT NOT NULL,
-          storage_nickname       TEXT,
-          time_added             TIMESTAMPTZ NOT NULL DEFAULT now(),
-          storage_used_bytes     BIGINT,
-          storage_total_bytes    BIGINT
+    Ok(())
+}
+
+/// Applies migrations newer than the recorded version. Must run under the
+/// migration lock.
+async fn apply_pending(conn: &mut PoolConnection<Postgres>) -> Result<Vec<i64>> {
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0)::BIGINT AS version FROM schema_version")
+        .fetch_one(&mut **conn)
+        .await?;
+    let current: i64 = row.get("version");
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = conn.begin().await?;
+
+        (&mut *tx).execute(migration.sql).await.with_context(|| {
+            format!("migration {} ({}) failed", migration.version, migration.name)
+        })?;
+
+        sqlx::query("INSERT INTO schema_version (version, name) VALUES ($1, $2)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        tracing::info!(
+            "Applied schema migration {} ({})",
+            migration.version,
+            migration.name
         );
-    "#).await?;
+        applied.push(migration.version);
+    }
 
-    Ok(pool)
+    Ok(applied)
 }
@@ -1,63 +1,90 @@
 use lettre::{
-    message::Mailbox,
+    message::{Mailbox, MultiPart},
     transport::smtp::{authentication::Credentials, AsyncSmtpTransport},
     AsyncTransport, Tokio1Executor, Message,
 };
 use crate::{
-    fail, ok, start,
-    config::{Config},
+    fail, ok,
+    config::{Config, SmtpTlsMode},
+    metrics,
 };
 use axum::http::StatusCode;
-use std::str::FromStr;
+use std::time::Duration;
 
 pub struct EmailClient {
     mailer: AsyncSmtpTransport<Tokio1Executor>,
     from:   Mailbox,
-    to:     Mailbox,
+    to:     Vec<Mailbox>,
+    cc:     Vec<Mailbox>,
+    bcc:    Vec<Mailbox>,
 }
 
 impl EmailClient {
     /// Build an EmailClient from your Config, or return the appropriate axum error.
     pub fn from_config(cfg: &Config) -> Result<Self, (StatusCode, &'static str)> {
-        let host = cfg.smtp_host.as_deref().ok_or_else(|| {
-            fail!(cfg, "SMTP config error", "SMTP_HOST not configured{}", "")
-        })?;
+        Self::build(cfg).map_err(|e| fail!(cfg, "SMTP config error", "{}", e))
+    }
+
+    /// Build an EmailClient from your Config, describing what is misconfigured
+    /// on failure. Unlike `from_config`, nothing is logged or reported, so
+    /// probes can call it as often as they like.
+    pub fn build(cfg: &Config) -> Result<Self, String> {
+        let host = cfg.smtp_host.as_deref().ok_or("SMTP_HOST not configured")?;
         let user = cfg.smtp_username.clone().unwrap_or_default();
         let pass = cfg.smtp_password.clone().unwrap_or_default();
-        let from_addr = cfg.email_from.as_deref().ok_or_else(|| {
-            fail!(cfg, "SMTP config error", "EMAIL_FROM not configured{}", "")
-        })?;
-        let to_addr = cfg.email_to.as_deref().ok_or_else(|| {
-            fail!(cfg, "SMTP config error", "EMAIL_TO not configured{}", "")
-        })?;
+        let from = cfg.email_from.clone().ok_or("EMAIL_FROM not configured")?;
+        let (to, cc, bcc) = (cfg.email_to.clone(), cfg.email_cc.clone(), cfg.email_bcc.clone());
 
-        let from = Mailbox::from_str(from_addr).expect("valid EMAIL_FROM");
-        let to   = Mailbox::from_str(to_addr).expect("valid EMAIL_TO");
+        if to.is_empty() && cc.is_empty() && bcc.is_empty() {
+            return Err("EMAIL_TO not configured".into());
+        }
 
-        let creds  = Credentials::new(user, pass);
-        let mailer = AsyncSmtpTransport::<Tokio1Executor>::relay(host)
-            .map_err(|e| {
-                fail!(cfg, "SMTP config error", "SMTP relay config failed: {}", e)
-            })?
-            .credentials(creds)
-            .build();
+        // Pick the transport for the configured TLS mode
+        let mut builder = match cfg.smtp_tls {
+            SmtpTlsMode::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .map_err(|e| format!("SMTP relay config failed: {}", e))?,
+            SmtpTlsMode::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| format!("SMTP STARTTLS relay config failed: {}", e))?,
+            SmtpTlsMode::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = cfg.smtp_port {
+            builder = builder.port(port);
+        }
+        builder = builder.timeout(Some(Duration::from_secs(cfg.smtp_timeout)));
 
-        Ok(EmailClient { mailer, from, to })
+        // Only authenticate when a username is configured
+        if !user.is_empty() {
+            builder = builder.credentials(Credentials::new(user, pass));
+        }
+        let mailer = builder.build();
+
+        Ok(EmailClient { mailer, from, to, cc, bcc })
     }
 
-    /// Send an HTML email with the given subject and HTML body.
-    pub async fn send_html(
+    /// Send a multipart/alternative email with the given subject, HTML body
+    /// and plain-text body, for clients (and spam filters) that prefer text.
+    pub async fn send(
         &self,
         subject: &str,
         html_body: String,
+        text_body: String,
         cfg: &Config,
     ) -> Result<(), (StatusCode, &'static str)> {
-        let email = Message::builder()
+        let mut builder = Message::builder()
             .from(self.from.clone())
-            .to(self.to.clone())
-            .header(lettre::message::header::ContentType::TEXT_HTML)
-            .subject(subject)
-            .body(html_body)
+            .subject(subject);
+        for mb in &self.to {
+            builder = builder.to(mb.clone());
+        }
+        for mb in &self.cc {
+            builder = builder.cc(mb.clone());
+        }
+        for mb in &self.bcc {
+            builder = builder.bcc(mb.clone());
+        }
+
+        let email = builder
+            .multipart(MultiPart::alternative_plain_html(text_body, html_body))
             .map_err(|e| {
                 fail!(cfg, "Email build error", "Failed to build email: {}", e)
             })?;
@@ -66,8 +93,34 @@ impl EmailClient {
             .send(email)
             .await
             .map_err(|e| {
+                metrics::add(&metrics::EMAILS_FAILED, 1);
                 fail!(cfg, "Email send error", "Failed to send email: {}", e)
             })
-            .map(|_| ok!(cfg, "Email '{}' sent successfully to {:?}", subject, self.to))
+            .map(|_| {
+                metrics::add(&metrics::EMAILS_SENT, 1);
+                ok!(cfg, "Email '{}' sent successfully ({})", subject, self.recipients())
+            })
     }
-}
\ No newline at end of file
+
+    /// Connects to the SMTP server and says hello, without sending anything.
+    pub async fn test_connection(&self) -> Result<(), String> {
+        match self.mailer.test_connection().await {
+            Ok(true)  => Ok(()),
+            Ok(false) => Err("SMTP server did not accept the connection".into()),
+            Err(e)    => Err(e.to_string()),
+        }
+    }
+
+    /// Human-readable list of every recipient, e.g. `to: a@x, b@x; bcc: c@x`.
+    pub fn recipients(&self) -> String {
+        let join = |list: &[Mailbox]| {
+            list.iter().map(|mb| mb.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        [("to", &self.to), ("cc", &self.cc), ("bcc", &self.bcc)]
+            .iter()
+            .filter(|(_, list)| !list.is_empty())
+            .map(|(label, list)| format!("{}: {}", label, join(list)))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
@@ -0,0 +1,208 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header::{ACCEPT, CONTENT_DISPOSITION, CONTENT_TYPE}, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, TryStreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{postgres::PgRow, FromRow, PgPool};
+use tokio::sync::mpsc;
+
+use crate::{
+    handlers::COMBINED_STATS_SELECT,
+    models::{CombinedStats, DbStorageRow, ExportQuery},
+};
+
+/// Rows are sent to the client in chunks of about this many bytes
+const CHUNK_BYTES: usize = 64 * 1024;
+/// Chunks buffered ahead of a slow client before reading from the database pauses
+const CHUNKS_AHEAD: usize = 4;
+
+/// Columns of `/export/events`, in `CombinedStats` order
+const EVENT_COLUMNS: &[&str] = &[
+    "summary_id", "created_at", "task", "time", "event", "repo", "plan", "snapshot", "error",
+    "message_type", "ss_error", "during", "item",
+    "files_new", "files_changed", "files_unmodified",
+    "dirs_new", "dirs_changed", "dirs_unmodified",
+    "data_blobs", "tree_blobs", "data_added",
+    "total_files_processed", "total_bytes_processed", "total_duration", "ss_snapshot",
+    "percent_done", "total_files", "files_done", "total_bytes", "bytes_done", "current_files",
+];
+
+/// Columns of `/export/storage`, in `DbStorageRow` order
+const STORAGE_COLUMNS: &[&str] = &[
+    "storage_location", "storage_nickname", "storage_used_bytes", "storage_total_bytes", "time_added",
+];
+
+/// Output format of an export
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    /// The `format` query parameter if given, otherwise the `Accept` header;
+    /// CSV when neither names a format.
+    pub fn choose(query: Option<&str>, headers: &HeaderMap) -> Result<Self, (StatusCode, &'static str)> {
+        match query {
+            Some("csv") => return Ok(ExportFormat::Csv),
+            Some("ndjson") => return Ok(ExportFormat::Ndjson),
+            Some(_) => return Err((StatusCode::BAD_REQUEST, "format must be csv or ndjson")),
+            None => {}
+        }
+        let accept = headers.get(ACCEPT).and_then(|v| v.to_str().ok()).unwrap_or("");
+        if accept.contains("ndjson") {
+            Ok(ExportFormat::Ndjson)
+        } else {
+            Ok(ExportFormat::Csv)
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv    => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv    => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Streams every event between `start` and `end`, with its snapshot stats,
+/// oldest first.
+pub fn export_events(pool: PgPool, query: &ExportQuery, format: ExportFormat) -> Response {
+    let sql = format!(
+        "{} WHERE s.time BETWEEN $1 AND $2 ORDER BY s.time, s.id",
+        COMBINED_STATS_SELECT
+    );
+    stream_rows::<CombinedStats>(pool, sql, query, format, "events", EVENT_COLUMNS)
+}
+
+/// Streams every storage sample taken between `start` and `end`, oldest first.
+pub fn export_storage(pool: PgPool, query: &ExportQuery, format: ExportFormat) -> Response {
+    let sql = r#"
+        SELECT storage_location, storage_nickname, storage_used_bytes, storage_total_bytes, time_added
+          FROM storage
+         WHERE time_added BETWEEN $1 AND $2
+         ORDER BY time_added, id
+    "#
+    .to_string();
+    stream_rows::<DbStorageRow>(pool, sql, query, format, "storage", STORAGE_COLUMNS)
+}
+
+/// Runs `sql` with the query's range on its own task and streams the rows as
+/// they arrive, so an export never holds more than a few chunks in memory.
+/// A database error mid-export cuts the response short.
+fn stream_rows<T>(
+    pool: PgPool,
+    sql: String,
+    query: &ExportQuery,
+    format: ExportFormat,
+    name: &'static str,
+    columns: &'static [&'static str],
+) -> Response
+where
+    T: for<'r> FromRow<'r, PgRow> + Serialize + Send + Unpin + 'static,
+{
+    let (start, end) = (query.start, query.end);
+    let (tx, rx) = mpsc::channel::<Result<Bytes, std::io::Error>>(CHUNKS_AHEAD);
+
+    // 1) Read rows on a separate task, pausing while the client catches up
+    tokio::spawn(async move {
+        let mut chunk = String::new();
+        if format == ExportFormat::Csv {
+            chunk.push_str(&columns.join(","));
+            chunk.push_str("\r\n");
+        }
+
+        let mut rows = sqlx::query_as::<_, T>(&sql).bind(start).bind(end).fetch(&pool);
+        let mut count = 0u64;
+        loop {
+            match rows.try_next().await {
+                Ok(Some(row)) => {
+                    write_row(&mut chunk, &row, format, columns);
+                    count += 1;
+                    if chunk.len() >= CHUNK_BYTES && tx.send(Ok(Bytes::from(std::mem::take(&mut chunk)))).await.is_err() {
+                        // Client went away
+                        return;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!("Export of {} failed after {} row(s): {}", name, count, e);
+                    let _ = tx.send(Err(std::io::Error::other(e))).await;
+                    return;
+                }
+            }
+        }
+        if !chunk.is_empty() {
+            let _ = tx.send(Ok(Bytes::from(chunk))).await;
+        }
+        tracing::info!("Exported {} {} row(s)", count, name);
+    });
+
+    // 2) Hand the chunks to the response body as they come
+    let body = stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+    let filename = format!(
+        "attachment; filename=\"{}-{}-{}.{}\"",
+        name,
+        start.format("%Y%m%d"),
+        end.format("%Y%m%d"),
+        format.extension()
+    );
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, HeaderValue::from_static(format.content_type()))
+        .header(CONTENT_DISPOSITION, filename)
+        .body(Body::from_stream(body))
+        .unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+/// Appends one row as a CSV line or a JSON line.
+fn write_row<T: Serialize>(out: &mut String, row: &T, format: ExportFormat, columns: &[&str]) {
+    match format {
+        ExportFormat::Ndjson => {
+            out.push_str(&serde_json::to_string(row).unwrap_or_default());
+            out.push('\n');
+        }
+        ExportFormat::Csv => {
+            let value = serde_json::to_value(row).unwrap_or(Value::Null);
+            let fields: Vec<String> = columns
+                .iter()
+                .map(|column| csv_field(value.get(*column).unwrap_or(&Value::Null)))
+                .collect();
+            out.push_str(&fields.join(","));
+            out.push_str("\r\n");
+        }
+    }
+}
+
+/// One CSV field: empty for null, quoted when it holds a comma, quote or line break.
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Checks that the export range is in order.
+pub fn check_range(start: DateTime<Utc>, end: DateTime<Utc>) -> Result<(), (StatusCode, &'static str)> {
+    if start > end {
+        return Err((StatusCode::BAD_REQUEST, "start must not be after end"));
+    }
+    Ok(())
+}
@@ -0,0 +1,290 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    alerts::{claim_alert, render_alert},
+    config::Config,
+    handlers::load_storage_stats,
+    html_report::format_bytes,
+    models::{CurrentStorageStats, StorageForecast},
+    notify::{notify_all, Notification, NotifyLevel},
+};
+
+/// Fewest samples a forecast is fitted to
+const MIN_SAMPLES: usize = 3;
+/// Shortest span of samples a forecast is fitted to, in days
+const MIN_SPAN_DAYS: f64 = 1.0;
+/// Samples beyond this are thinned out evenly, as the fit compares every pair
+const MAX_SAMPLES: usize = 400;
+/// Minimum time between forecast alerts for the same mount and level
+const ALERT_COOLDOWN_HOURS: i64 = 24;
+
+/// Projects when the mount at `location` reaches the warning threshold and
+/// fills up, from its samples over the last `FORECAST_WINDOW_DAYS` up to `at`.
+///
+/// The growth rate is the Theil–Sen estimate (median slope between every pair
+/// of samples), so a one-off spike or cleanup doesn't skew it. The projection
+/// starts from the current usage. Returns `None` with too few samples.
+pub async fn forecast_storage(
+    pool: &PgPool,
+    cfg: &Config,
+    location: &str,
+    used_bytes: i64,
+    total_bytes: i64,
+    at: DateTime<Utc>,
+) -> Result<Option<StorageForecast>, sqlx::Error> {
+    // 1) Samples within the window
+    let rows: Vec<(DateTime<Utc>, i64)> = sqlx::query_as(
+        r#"
+        SELECT time_added, storage_used_bytes
+          FROM storage
+         WHERE storage_location = $1
+           AND time_added >  $2
+           AND time_added <= $3
+         ORDER BY time_added
+        "#,
+    )
+    .bind(location)
+    .bind(at - Duration::days(cfg.forecast.window_days))
+    .bind(at)
+    .fetch_all(pool)
+    .await?;
+
+    // 2) Growth per day
+    let Some(growth) = growth_per_day(&rows) else {
+        return Ok(None);
+    };
+
+    // 3) Time until each threshold, from the current usage
+    let warn_bytes = total_bytes as f64 * cfg.forecast.warn_percent / 100.0;
+    let days_until = |target: f64| -> Option<f64> {
+        if used_bytes as f64 >= target {
+            Some(0.0)
+        } else if growth > 0.0 {
+            Some((target - used_bytes as f64) / growth)
+        } else {
+            None
+        }
+    };
+    // Dates more than a century out are left open
+    let date_after = |days: Option<f64>| {
+        days.filter(|&d| d < 36_500.0)
+            .and_then(|d| at.checked_add_signed(Duration::seconds((d * 86_400.0) as i64)))
+    };
+    let days_until_warn = days_until(warn_bytes);
+    let days_until_full = days_until(total_bytes as f64);
+
+    Ok(Some(StorageForecast {
+        growth_bytes_per_day: growth,
+        samples:              rows.len() as i64,
+        window_days:          cfg.forecast.window_days,
+        warn_percent:         cfg.forecast.warn_percent,
+        days_until_warn,
+        warn_at:              date_after(days_until_warn),
+        days_until_full,
+        full_at:              date_after(days_until_full),
+    }))
+}
+
+/// Theil–Sen slope of used bytes over time, in bytes per day, or `None` if
+/// the samples are too few or too close together.
+fn growth_per_day(rows: &[(DateTime<Utc>, i64)]) -> Option<f64> {
+    if rows.len() < MIN_SAMPLES {
+        return None;
+    }
+    let first = rows[0].0;
+    let span = (rows[rows.len() - 1].0 - first).num_seconds() as f64 / 86_400.0;
+    if span < MIN_SPAN_DAYS {
+        return None;
+    }
+
+    // 1) Thin out evenly, always keeping the latest sample
+    let step = rows.len().div_ceil(MAX_SAMPLES);
+    let to_point = |(t, used): &(DateTime<Utc>, i64)| ((*t - first).num_seconds() as f64 / 86_400.0, *used as f64);
+    let mut points: Vec<(f64, f64)> = rows.iter().step_by(step).map(to_point).collect();
+    let last = to_point(&rows[rows.len() - 1]);
+    if points.last() != Some(&last) {
+        points.push(last);
+    }
+    points.dedup_by(|a, b| a.0 == b.0);
+
+    // 2) Median of the slopes between every pair
+    let mut slopes = Vec::with_capacity(points.len() * (points.len() - 1) / 2);
+    for (i, a) in points.iter().enumerate() {
+        for b in &points[i + 1..] {
+            slopes.push((b.1 - a.1) / (b.0 - a.0));
+        }
+    }
+    if slopes.is_empty() {
+        return None;
+    }
+    slopes.sort_by(|a, b| a.total_cmp(b));
+    let mid = slopes.len() / 2;
+    Some(if slopes.len() % 2 == 0 {
+        (slopes[mid - 1] + slopes[mid]) / 2.0
+    } else {
+        slopes[mid]
+    })
+}
+
+/// Alerts for every mount projected to fill up or reach the warning threshold
+/// within `FORECAST_LEAD_DAYS`, at most once a day per mount and level.
+/// Failures are logged only.
+pub async fn check_storage_forecasts(cfg: Config, pool: PgPool) {
+    if cfg.forecast.lead_days == 0 {
+        return;
+    }
+
+    // 1) Latest stats, with forecasts
+    let stats = match load_storage_stats(&pool, &cfg).await {
+        Ok(s) => s,
+        Err(_) => return, // already logged and reported
+    };
+
+    let lead = cfg.forecast.lead_days as f64;
+    for stat in &stats {
+        // 2) Only growing mounts that cross a threshold within the lead time
+        let Some(f) = stat.forecast.as_ref().filter(|f| f.growth_bytes_per_day > 0.0) else {
+            continue;
+        };
+        let level = match (f.days_until_full, f.days_until_warn) {
+            (Some(d), _) if d <= lead => NotifyLevel::Error,
+            (_, Some(d)) if d <= lead => NotifyLevel::Warning,
+            _ => continue,
+        };
+
+        // 3) Rate limit per mount and level
+        let key = format!("forecast:{}:{}", level.as_str(), stat.location);
+        match claim_alert(&pool, &key, Duration::hours(ALERT_COOLDOWN_HOURS)).await {
+            Ok(Some(_)) => {}
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::error!("Alert lookup for forecast of {} failed: {}", stat.location, e);
+                continue;
+            }
+        }
+
+        let notification = render_forecast_alert(&cfg, stat, f, level);
+        match notify_all(&cfg, &notification).await {
+            Ok(delivered) => tracing::info!(
+                "Alert '{}' sent to {} channel(s)",
+                notification.title,
+                delivered
+            ),
+            Err((_, msg)) => tracing::error!("Alert '{}' failed: {}", notification.title, msg),
+        }
+    }
+}
+
+/// Builds the alert for a mount that is filling up.
+fn render_forecast_alert(
+    cfg: &Config,
+    stat: &CurrentStorageStats,
+    f: &StorageForecast,
+    level: NotifyLevel,
+) -> Notification {
+    let name = stat.nickname.as_deref().filter(|s| !s.is_empty()).unwrap_or(&stat.location);
+    let title = if level == NotifyLevel::Error {
+        format!("💽 Storage full soon: {}", name)
+    } else {
+        format!("💽 Storage nearly full soon: {}", name)
+    };
+
+    let used = format!(
+        "{} of {} ({:.1}%)",
+        format_bytes(stat.current.used_bytes.max(0) as u64),
+        format_bytes(stat.current.total_bytes.max(0) as u64),
+        stat.current.percent_used
+    );
+    let warn_label = format!("{}% used", f.warn_percent);
+
+    render_alert(
+        cfg,
+        title,
+        level,
+        &[
+            ("Mount",     stat.location.clone()),
+            ("Used",      used),
+            ("Growth",    format!("{} per day", format_bytes(f.growth_bytes_per_day as u64))),
+            (&warn_label, format_projection(f.days_until_warn, f.warn_at)),
+            ("Full",      format_projection(f.days_until_full, f.full_at)),
+        ],
+        None,
+        &format!(
+            "Projected from {} samples over the last {} days. Free up space or grow the volume before then.",
+            f.samples, f.window_days
+        ),
+    )
+}
+
+/// `in 12 days (Oct 29, 2026)`, `now` once reached, or `never` when not
+/// growing.
+pub fn format_projection(days: Option<f64>, at: Option<DateTime<Utc>>) -> String {
+    match (days, at) {
+        (Some(d), _) if d <= 0.0 => "now".into(),
+        (Some(d), Some(at))      => format!("in {} ({})", format_days(d), at.with_timezone(&Local).format("%b %-d, %Y")),
+        (Some(d), None)          => format!("in {}", format_days(d)),
+        _                        => "never".into(),
+    }
+}
+
+/// `1 day`, `12 days`, rounded up
+fn format_days(days: f64) -> String {
+    match days.ceil() as i64 {
+        1 => "1 day".into(),
+        n => format!("{} days", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const GB: i64 = 1_000_000_000;
+
+    /// One sample every `hours`, used bytes given by `used(i)`
+    fn samples(count: usize, hours: i64, used: impl Fn(usize) -> i64) -> Vec<(DateTime<Utc>, i64)> {
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        (0..count)
+            .map(|i| (start + Duration::hours(hours * i as i64), used(i)))
+            .collect()
+    }
+
+    #[test]
+    fn steady_growth() {
+        // Hourly for 30 days, more than MAX_SAMPLES, so every other sample is
+        // kept; the latest falls between and is added back
+        let rows = samples(24 * 30 + 2, 1, |i| 100 * GB + i as i64 * GB / 24);
+
+        let growth = growth_per_day(&rows).unwrap();
+        assert!((growth - GB as f64).abs() < 1.0, "growth {}", growth);
+    }
+
+    #[test]
+    fn single_spike() {
+        // One sample reads 50 GB high; the median ignores it
+        let rows = samples(10, 24, |i| if i == 6 { 150 * GB } else { 100 * GB + i as i64 * GB });
+
+        let growth = growth_per_day(&rows).unwrap();
+        assert!((growth - GB as f64).abs() < 1.0, "growth {}", growth);
+    }
+
+    #[test]
+    fn shrinking() {
+        let mut rows = samples(10, 24, |i| 100 * GB - i as i64 * 2 * GB);
+        // A sample repeated with the same timestamp is dropped, not divided by zero
+        rows.insert(5, rows[4]);
+
+        let growth = growth_per_day(&rows).unwrap();
+        assert!((growth + 2.0 * GB as f64).abs() < 1.0, "growth {}", growth);
+    }
+
+    #[test]
+    fn too_few_samples() {
+        // Fewer than MIN_SAMPLES
+        assert_eq!(growth_per_day(&samples(2, 24, |i| i as i64 * GB)), None);
+        // Enough samples, but spanning less than MIN_SPAN_DAYS
+        assert_eq!(growth_per_day(&samples(10, 1, |i| i as i64 * GB)), None);
+    }
+}
@@ -1,31 +1,48 @@
 use axum::{
-    extract::{ConnectInfo, Json, State},
-    http::{HeaderMap, StatusCode},
+    body::Bytes,
+    extract::{ConnectInfo, Json, Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderValue, StatusCode},
     response::IntoResponse,
 };
 use chrono::{DateTime, Duration, Local, Utc};
 use fs2::{free_space, total_space};
-use std::{fs, net::SocketAddr};
-use sqlx::{PgPool, Row};
+use minijinja::context;
+use std::{collections::HashMap, fs, net::SocketAddr};
+use sqlx::{Connection, PgConnection, PgPool, Postgres, QueryBuilder, Row};
 use crate::{
-    fail, ok, start, warn,
-    config::{Config},
+    fail, ok, warn,
+    alerts::send_event_alert,
+    charts::load_report_trends,
+    config::{ComparePeriods, Config, ReportContent},
+    db::lock_ingest_shared,
+    forecast::{check_storage_forecasts, forecast_storage},
     email::{EmailClient},
-    healthcheck::{
-        ping_healthcheck,
-        HealthStatus
-    },
+    export::{check_range, export_events, export_storage, ExportFormat},
+    jobs::job_statuses,
+    metrics::{self, render_metrics},
     html_report::{format_range_iso_with_offset, prune_old_reports, render_report_html, write_report_html},
-    models::{CombinedStats, CurrentStorageStats, DbStorageRow, EventTotals, EventTotalsReport, GenerateReport, PeriodStats, StatsRequest, StorageStatsRequest, SummaryPayload, StorageReport},
-    utils::{format_local_datetime},
+    missed::find_overdue,
+    reports::{find_report, send_scheduled_report},
+    models::{AddEventResponse, AddEventsResponse, BatchItemResult, CombinedStats, CurrentStorageStats, DbStorageRow, EventDetail, EventTime, EventTotals, EventTotalsReport, EventsPage, EventsQuery, ExportQuery, GenerateReport, GroupTotalsReport, NamedEventTotals, PeriodStats, StatsRequest, StorageStatsRequest, SummaryPayload, StorageReport, TotalsBreakdown, TotalsGroup},
+    notify::{notify_all, render_report_markdown, Notification, NotifyLevel},
+    probes::check_readiness,
+    templates,
+    thresholds::{check_storage_thresholds, usage_level},
+    text_report::{render_report_text, render_test_text},
 };
 
-///
-/// HANDLER METHODS
-/// 
+//
+// HANDLER METHODS
+//
 
 /// POST `/add-event` endpoint.
 /// Inserts snapshot summary and statistics into the database.
+///
+/// Replays (same repo, plan, snapshot, event and time, or a repeated
+/// `Idempotency-Key` header) are not stored again; the original summary id is
+/// returned with `200 OK` instead of `201 Created`.
+///
+/// New error (and optionally warning) events trigger an immediate alert.
 pub async fn add_event_handler(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State((pool, cfg)): State<(PgPool, Config)>,
@@ -35,9 +52,30 @@ pub async fn add_event_handler(
     // 1) Auth
     validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
 
-    // 2) Insert the new summary
-    let (summary_id, created) =
-        insert_summary_with_stats(&cfg, &pool, &payload).await?;
+    // 2) Insert the new summary, or find the one it replays
+    let idempotency_key = headers
+        .get("Idempotency-Key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+    let (summary_id, created, inserted) =
+        insert_summary_with_stats(&cfg, &pool, &payload, idempotency_key).await?;
+
+    let response = AddEventResponse {
+        summary_id,
+        created_at: created,
+        duplicate: !inserted,
+    };
+
+    if !inserted {
+        tracing::info!(
+            "Duplicate event from {} matches existing ID {}, skipping insert",
+            addr,
+            summary_id
+        );
+        return Ok((StatusCode::OK, Json(response)));
+    }
+    metrics::add(&metrics::EVENTS_INGESTED, 1);
 
     tracing::info!(
         "Event with ID {} at {} from {}",
@@ -46,10 +84,116 @@ pub async fn add_event_handler(
         addr
     );
 
-    // 3) Update the storage stats post-event
+    // 3) Alert right away on failures, without holding up the response
+    tokio::spawn(send_event_alert(cfg.clone(), pool.clone(), payload));
+
+    // 4) Update the storage stats post-event
     load_and_insert_storage_stats(&pool, &cfg).await?;
 
-    Ok((StatusCode::OK, "ok"))
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// POST `/add-events` endpoint.
+/// Inserts a batch of events in one transaction, for backfilling history.
+///
+/// Accepts either a JSON array of events or NDJSON (one event per line) when
+/// the `Content-Type` is `application/x-ndjson`. Items that fail to parse or
+/// insert are reported individually; the rest of the batch is still stored.
+/// Storage statistics are refreshed once for the whole batch.
+pub async fn add_events_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    // 1) Auth
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+
+    // 2) Parse the body into individual items, keeping per-item parse errors
+    let is_ndjson = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("ndjson"))
+        .unwrap_or(false);
+
+    let items: Vec<Result<SummaryPayload, String>> = if is_ndjson {
+        let text = std::str::from_utf8(&body)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Body is not valid UTF-8"))?;
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| e.to_string()))
+            .collect()
+    } else {
+        let values: Vec<serde_json::Value> = serde_json::from_slice(&body)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Body must be a JSON array of events"))?;
+        values
+            .into_iter()
+            .map(|v| serde_json::from_value(v).map_err(|e| e.to_string()))
+            .collect()
+    };
+
+    // 3) Insert every item that parsed, in one transaction
+    let total = items.len();
+    let mut parse_errors: Vec<Option<String>> = Vec::with_capacity(total);
+    let mut payloads = Vec::with_capacity(total);
+    for item in items {
+        match item {
+            Ok(payload) => {
+                payloads.push(payload);
+                parse_errors.push(None);
+            }
+            Err(e) => parse_errors.push(Some(e)),
+        }
+    }
+    let mut outcomes = insert_summaries_batch(&cfg, &pool, &payloads)
+        .await?
+        .into_iter();
+
+    // 4) Stitch parse failures and insert outcomes back together, in order
+    let mut response = AddEventsResponse {
+        inserted:   0,
+        duplicates: 0,
+        failed:     0,
+        results:    Vec::with_capacity(total),
+    };
+    for (index, parse_error) in parse_errors.into_iter().enumerate() {
+        let outcome = match parse_error {
+            Some(e) => Err(e),
+            None => outcomes.next().unwrap_or_else(|| Err("missing insert result".into())),
+        };
+        let result = match outcome {
+            Ok((summary_id, _, inserted)) => {
+                if inserted {
+                    response.inserted += 1;
+                } else {
+                    response.duplicates += 1;
+                }
+                BatchItemResult { index, summary_id: Some(summary_id), duplicate: !inserted, error: None }
+            }
+            Err(e) => {
+                response.failed += 1;
+                BatchItemResult { index, summary_id: None, duplicate: false, error: Some(e) }
+            }
+        };
+        response.results.push(result);
+    }
+
+    metrics::add(&metrics::EVENTS_INGESTED, response.inserted as u64);
+    tracing::info!(
+        "Batch of {} events from {}: {} inserted, {} duplicates, {} failed",
+        total,
+        addr,
+        response.inserted,
+        response.duplicates,
+        response.failed
+    );
+
+    // 5) Update the storage stats once for the whole batch
+    if response.inserted > 0 {
+        load_and_insert_storage_stats(&pool, &cfg).await?;
+    }
+
+    Ok((StatusCode::OK, Json(response)))
 }
 
 /// POST `/get-events-in-range` endpoint.
@@ -64,7 +208,7 @@ pub async fn get_events_in_range_handler(
     validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
 
     // 2) Delegate to helper to fetch the combined stats between the request start and end date
-    let rows = fetch_combined_stats(&cfg, &pool, req.start_date, req.end_date).await?;
+    let rows = fetch_combined_stats(&cfg, &pool, req.start_date, req.end_date, EventTime::Occurred).await?;
 
     // 3) Return the formatted JSON
     Ok((StatusCode::OK, Json(rows)))
@@ -82,12 +226,31 @@ pub async fn get_events_in_range_totals_handler(
     validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
 
     // 2) Fetch the aggregated totals
-    let totals = load_event_totals_report(&cfg, &pool, req.start_date, req.end_date).await?;
+    let totals = load_event_totals_report(&cfg, &pool, req.start_date, req.end_date, EventTime::Occurred).await?;
 
     // 3) Return JSON
     Ok((StatusCode::OK, Json(totals)))
 }
 
+/// POST `/get-events-in-range-breakdown` endpoint.
+/// Takes in a start_date and end_date, returns the totals per repo and per plan
+/// with their previous day, week, and month
+pub async fn get_events_in_range_breakdown_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    headers: HeaderMap,
+    Json(req): Json<StatsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    // 1) Auth
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+
+    // 2) Fetch the grouped totals
+    let breakdown = load_totals_breakdown(&cfg, &pool, req.start_date, req.end_date, EventTime::Occurred).await?;
+
+    // 3) Return JSON
+    Ok((StatusCode::OK, Json(breakdown)))
+}
+
 /// GET `/send-test-email` endpoint.
 /// Send a test email using the configured SMTP settings.
 pub async fn send_test_email_handler(
@@ -101,18 +264,12 @@ pub async fn send_test_email_handler(
     // 2) Ensure we have all SMTP settings
     let client = EmailClient::from_config(&cfg)?;
 
-    // 3) Read the test email HTML
-    let mut html = fs::read_to_string("html/test_email.html")
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read HTML file"))?;
+    // 3) Render the test email HTML
+    let html = templates::render(&cfg, "test_email.html", context! {})
+        .map_err(|e| fail!(cfg, "Template error", "failed to render test_email.html: {:#}", e))?;
 
-    // 4) Modify the fields
-    html = html.replace("{{TIMESTAMP}}", &format_local_datetime(Local::now()));
-    html = html.replace("{{BACKREST_URL}}", &cfg.backrest_url.clone().unwrap_or_default());
-    html = html.replace("{{PGADMIN_URL}}", &cfg.pgadmin_url.clone().unwrap_or_default());
-    html = html.replace("{{VERSION}}", &cfg.version.to_string());
-
-    // 5) Build the email and send
-    client.send_html("🚀 Test Email", html, &cfg).await?;
+    // 4) Build the email and send
+    client.send("🚀 Test Email", html, render_test_text(&cfg), &cfg).await?;
 
     Ok((StatusCode::OK, "Test email sent"))
 }
@@ -131,7 +288,12 @@ pub async fn update_storage_statistics_handler(
     // 2) Delegate insertion & report generation
     let reports = load_and_insert_storage_stats(&pool, &cfg).await?;
 
-    // 3) Log & return
+    // 3) Alert on mounts projected to fill up, without holding up the caller
+    if !reports.is_empty() {
+        tokio::spawn(check_storage_forecasts(cfg.clone(), pool.clone()));
+    }
+
+    // 4) Log & return
     ok!(cfg, "Storage statistics updated from {}.", addr);
 
     Ok((StatusCode::OK, Json(reports)))
@@ -151,6 +313,149 @@ pub async fn get_latest_storage_stats_handler(
     Ok((StatusCode::OK, Json(stats)))
 }
 
+/// GET `/events` endpoint.
+/// Lists events with their snapshot stats, filtered, sorted and paged by the
+/// query parameters.
+pub async fn get_events_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    headers: HeaderMap,
+    Query(query): Query<EventsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+
+    let page = fetch_events_page(&cfg, &pool, &query).await?;
+
+    Ok((StatusCode::OK, Json(page)))
+}
+
+/// GET `/events/:summary_id` endpoint.
+/// Returns one event with its snapshot stats and the events related to it.
+pub async fn get_event_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    Path(summary_id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+
+    let detail = fetch_event_detail(&cfg, &pool, summary_id)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "Event not found"))?;
+
+    Ok((StatusCode::OK, Json(detail)))
+}
+
+/// GET `/snapshots/:snapshot_id` endpoint.
+/// Returns the event of a restic snapshot, found by its full or short ID, and
+/// the events related to it.
+pub async fn get_snapshot_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    Path(snapshot_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+
+    let summary_id = find_snapshot_event(&cfg, &pool, &snapshot_id)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "Snapshot not found"))?;
+    let detail = fetch_event_detail(&cfg, &pool, summary_id)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "Snapshot not found"))?;
+
+    Ok((StatusCode::OK, Json(detail)))
+}
+
+/// GET `/export/events` endpoint.
+/// Streams the events between `start` and `end`, joined with their snapshot
+/// stats, as CSV or NDJSON per the `format` parameter or `Accept` header.
+pub async fn export_events_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+    check_range(query.start, query.end)?;
+    let format = ExportFormat::choose(query.format.as_deref(), &headers)?;
+
+    Ok(export_events(pool, &query, format))
+}
+
+/// GET `/export/storage` endpoint.
+/// Streams the storage samples taken between `start` and `end`, as CSV or
+/// NDJSON per the `format` parameter or `Accept` header.
+pub async fn export_storage_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    headers: HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+    check_range(query.start, query.end)?;
+    let format = ExportFormat::choose(query.format.as_deref(), &headers)?;
+
+    Ok(export_storage(pool, &query, format))
+}
+
+/// GET `/jobs` endpoint.
+/// Lists the scheduled jobs with their cron expression, next fire time, and most recent runs.
+pub async fn get_jobs_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+
+    let jobs = job_statuses(&pool, &cfg)
+        .await
+        .map_err(|e| fail!(cfg, "DB error", "job status query failed: {}", e))?;
+
+    Ok((StatusCode::OK, Json(jobs)))
+}
+
+/// GET `/healthz` endpoint.
+/// Liveness probe: answers as long as the server is running. Not behind the API key.
+pub async fn healthz_handler(
+    State((_pool, cfg)): State<(PgPool, Config)>,
+) -> impl IntoResponse {
+    (StatusCode::OK, Json(serde_json::json!({ "status": "ok", "version": cfg.version })))
+}
+
+/// GET `/readyz` endpoint.
+/// Readiness probe: checks the database, storage mounts and optionally SMTP,
+/// returning `503` if any check fails. Not behind the API key.
+pub async fn readyz_handler(
+    State((pool, cfg)): State<(PgPool, Config)>,
+) -> impl IntoResponse {
+    let readiness = check_readiness(&pool, &cfg).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(readiness))
+}
+
+/// GET `/metrics` endpoint.
+/// Backup, storage and process metrics in the Prometheus text format. Not
+/// behind the API key, so Prometheus can scrape it as is.
+pub async fn get_metrics_handler(
+    State((pool, cfg)): State<(PgPool, Config)>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let body = render_metrics(&pool, &cfg)
+        .await
+        .map_err(|e| fail!(cfg, "DB error", "metrics query failed: {}", e))?;
+
+    Ok((
+        StatusCode::OK,
+        [(CONTENT_TYPE, HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"))],
+        body,
+    ))
+}
+
 /// POST `/get-storage-stats` endpoint.
 /// Retrieves the storage statistics for the provided end_date and its previous day, week, and month.
 pub async fn get_storage_stats_handler(
@@ -182,22 +487,37 @@ pub async fn get_events_and_storage_stats_handler(
     validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
 
     // 2) Fetch the combined event totals
-    let event_totals = load_event_totals_report(&cfg, &pool, req.start_date, req.end_date).await?;
+    let event_totals = load_event_totals_report(&cfg, &pool, req.start_date, req.end_date, EventTime::Occurred).await?;
 
     // 3) Delegate to helper to fetch the combined stats between the request start and end date
-    let snapshot_summaries = fetch_combined_stats(&cfg, &pool, req.start_date, req.end_date).await?;
+    let snapshot_summaries = fetch_combined_stats(&cfg, &pool, req.start_date, req.end_date, EventTime::Occurred).await?;
 
     // 4) Trigger an update to update the storage statistics
     load_and_insert_storage_stats(&pool, &cfg).await?;
     
     // 5) Get latest storage statistics
     let storage_statistics = load_storage_stats_at(&pool, &cfg, req.end_date).await?;
+
+    // 6) Check for plans or repos that missed an expected run
+    let overdue_schedules = find_overdue(&cfg, &pool, req.end_date).await?;
+
+    // 7) Break the totals down per repo and per plan
+    let totals_breakdown = load_totals_breakdown(&cfg, &pool, req.start_date, req.end_date, EventTime::Occurred).await?;
+
+    // 8) Daily history for the charts
+    let locations: Vec<String> = storage_statistics.iter().map(|s| s.location.clone()).collect();
+    let trends = load_report_trends(&cfg, &pool, req.end_date, &locations).await?;
     
-    // 6) Return the combined report
+    // 9) Return the combined report
     let payload = GenerateReport {
+        name: None,
+        compare: ComparePeriods::ALL,
         event_totals,
         snapshot_summaries,
         storage_statistics,
+        overdue_schedules,
+        totals_breakdown,
+        trends,
     };
     Ok((StatusCode::OK, Json(payload)))
 }
@@ -205,8 +525,8 @@ pub async fn get_events_and_storage_stats_handler(
 /// POST `/generate-and-send-email-report` endpoint.
 ///
 /// * Validates API key and caller IP.
-/// * Gathers event totals, snapshot summaries, and storage stats.
-/// * Renders the combined report to HTML, writes it to disk, and sends via email.
+/// * Generates the report for the requested window and sends it (see
+///   `generate_and_send_report`).
 ///
 /// # Errors
 /// Returns an unauthorized error if API key validation fails, or an internal error if any step fails.
@@ -216,31 +536,76 @@ pub async fn generate_and_send_email_report(
     headers: HeaderMap,
     Json(req): Json<StatsRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
-    // 1) Auth
     validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
 
-    // 2) Gather all pieces of the report
-    let event_totals       = load_event_totals_report(&cfg, &pool, req.start_date, req.end_date).await?;
-    let snapshot_summaries = fetch_combined_stats(&cfg, &pool, req.start_date, req.end_date).await?;
-    load_and_insert_storage_stats(&pool, &cfg).await?;
-    let storage_stats      = load_storage_stats_at(&pool, &cfg, req.end_date).await?;
+    generate_and_send_report(&cfg, &pool, None, &ReportContent::default(), req.start_date, req.end_date, EventTime::Occurred).await?;
 
-    let report = GenerateReport {
+    Ok((StatusCode::OK, "Report sent"))
+}
+
+/// POST `/reports/{name}/send` endpoint.
+/// Sends the named scheduled report now, with the window its next scheduled
+/// run would have (see `reports::send_scheduled_report`).
+pub async fn send_named_report_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State((pool, cfg)): State<(PgPool, Config)>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    validate_api_key_with_ip(&headers, &cfg.auth_key, addr)?;
+
+    let report = find_report(&cfg, &name).ok_or((StatusCode::NOT_FOUND, "Unknown report"))?;
+    let sent = send_scheduled_report(&cfg, &pool, report, Utc::now(), false).await?;
+
+    Ok((StatusCode::OK, Json(sent)))
+}
+
+/// Gathers event totals, snapshot summaries, and storage stats between `start`
+/// and `end`, placing events in the window by their `by` time, renders the combined report with `content`'s template, writes it
+/// to disk, and sends it to every notification channel. Emails go to
+/// `content`'s recipients if set. `name` labels a named report.
+///
+/// Returns the number of channels that delivered.
+pub async fn generate_and_send_report(
+    cfg: &Config,
+    pool: &PgPool,
+    name: Option<&str>,
+    content: &ReportContent,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    by: EventTime,
+) -> Result<usize, (StatusCode, &'static str)> {
+    // 1) Gather all pieces of the report
+    let event_totals       = load_event_totals_report(cfg, pool, start, end, by).await?;
+    let snapshot_summaries = fetch_combined_stats(cfg, pool, start, end, by).await?;
+    load_and_insert_storage_stats(pool, cfg).await?;
+    let storage_stats      = load_storage_stats_at(pool, cfg, end).await?;
+    let overdue_schedules  = find_overdue(cfg, pool, end).await?;
+    let totals_breakdown   = load_totals_breakdown(cfg, pool, start, end, by).await?;
+    let locations: Vec<String> = storage_stats.iter().map(|s| s.location.clone()).collect();
+    let trends             = load_report_trends(cfg, pool, end, &locations).await?;
+
+    let mut report = GenerateReport {
+        name: name.map(str::to_string),
+        compare: content.compare,
         event_totals,
         snapshot_summaries,
         storage_statistics: storage_stats,
+        overdue_schedules,
+        totals_breakdown,
+        trends,
     };
+    drop_unused_periods(&mut report);
 
-    // 3) Render the HTML body
-    let html = render_report_html(&cfg, &report)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // 2) Render the HTML body
+    let html = render_report_html(cfg, &content.template, &report)?;
 
-    // 4) Write to disk under a timestamped name, then prune old ones
+    // 3) Write to disk under a timestamped name, then prune old ones
     let now = Local::now();
-    let filename = format!(
-        "/reports/report-{}.html",
-        now.format("%Y-%m-%d_%H-%M-%S_%Z")
-    );
+    let filename = match name {
+        Some(name) => format!("/reports/report-{}-{}.html", name, now.format("%Y-%m-%d_%H-%M-%S_%Z")),
+        None       => format!("/reports/report-{}.html", now.format("%Y-%m-%d_%H-%M-%S_%Z")),
+    };
     write_report_html(&filename, &html)?;
     let max_files: usize = cfg
         .retained_reports
@@ -249,61 +614,216 @@ pub async fn generate_and_send_email_report(
     prune_old_reports("/reports", max_files)
         .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to prune old reports"))?;
     
-    let client = EmailClient::from_config(&cfg)?;
-    let current = &report.event_totals.current;
-    let emoji = status_emoji_for_event_totals(&report.event_totals.current);
-    let subject = format!(
-        "{} Backup Summary ({})",
-        emoji,
-        format_range_iso_with_offset(req.start_date, req.end_date)
-    );
-    client.send_html(&subject, html, &cfg).await?;
+    // 4) Fan out to every notification channel
+    // Missed runs count as errors, whatever the events say
+    let (emoji, level) = if report.overdue_schedules.is_empty() {
+        (
+            status_emoji_for_event_totals(&report.event_totals.current),
+            NotifyLevel::for_event_totals(&report.event_totals.current),
+        )
+    } else {
+        ("❌", NotifyLevel::Error)
+    };
+    let notification = Notification {
+        title: match name {
+            Some(name) => format!(
+                "{} Backup Summary: {} ({})",
+                emoji,
+                name,
+                format_range_iso_with_offset(start, end)
+            ),
+            None => format!(
+                "{} Backup Summary ({})",
+                emoji,
+                format_range_iso_with_offset(start, end)
+            ),
+        },
+        markdown: render_report_markdown(cfg, &report),
+        text: render_report_text(cfg, &report),
+        level,
+        html,
+    };
+    // A report's own recipients replace the global ones, CC and BCC included
+    let delivered = if content.has_recipients() {
+        let mut cfg = cfg.clone();
+        cfg.email_to  = content.email_to.clone().unwrap_or_default();
+        cfg.email_cc  = content.email_cc.clone().unwrap_or_default();
+        cfg.email_bcc = content.email_bcc.clone().unwrap_or_default();
+        notify_all(&cfg, &notification).await?
+    } else {
+        notify_all(cfg, &notification).await?
+    };
 
-    ok!(cfg, "Report email sent{}", "");
-    Ok((StatusCode::OK, "Report email sent"))
+    ok!(cfg, "Report sent to {} channel(s)", delivered);
+    Ok(delivered)
 }
 
-///
-/// DATABASE QUERY METHODS
-/// 
+/// Clears the comparison periods the report doesn't show, so every output
+/// leaves them out alike.
+fn drop_unused_periods(report: &mut GenerateReport) {
+    let compare = report.compare;
+
+    let totals = &mut report.event_totals;
+    totals.previous_day   = totals.previous_day.take().filter(|_| compare.day);
+    totals.previous_week  = totals.previous_week.take().filter(|_| compare.week);
+    totals.previous_month = totals.previous_month.take().filter(|_| compare.month);
+    for group in report
+        .totals_breakdown
+        .by_repo
+        .iter_mut()
+        .chain(report.totals_breakdown.by_plan.iter_mut())
+    {
+        group.previous_day   = group.previous_day.take().filter(|_| compare.day);
+        group.previous_week  = group.previous_week.take().filter(|_| compare.week);
+        group.previous_month = group.previous_month.take().filter(|_| compare.month);
+    }
+    for stat in &mut report.storage_statistics {
+        stat.previous_day   = stat.previous_day.take().filter(|_| compare.day);
+        stat.previous_week  = stat.previous_week.take().filter(|_| compare.week);
+        stat.previous_month = stat.previous_month.take().filter(|_| compare.month);
+    }
+}
+
+//
+// DATABASE QUERY METHODS
+//
 
-/// Inserts a new summary (and optional snapshot_stats), returning `(id, created_at)`.
+/// Inserts a new summary (and optional snapshot_stats), returning `(id, created_at, inserted)`.
+///
+/// If the payload replays an event that is already stored (matching natural key
+/// or idempotency key), nothing is written and the existing row's id and
+/// `created_at` are returned with `inserted = false`.
 pub async fn insert_summary_with_stats(
     _cfg: &Config,
     pool: &PgPool,
     payload: &SummaryPayload,
-) -> Result<(i32, DateTime<Utc>), (StatusCode, &'static str)> {
-    // 1) Determine created_at in UTC
+    idempotency_key: Option<&str>,
+) -> Result<(i32, DateTime<Utc>, bool), (StatusCode, &'static str)> {
+    let mut tx = pool.begin().await.map_err(|e| {
+        fail!(_cfg, "DB error", "DB begin transaction error: {}", e)
+    })?;
+
+    let inserted = insert_summary_on_conn(&mut tx, payload, idempotency_key)
+        .await
+        .map_err(|e| {
+            fail!(_cfg, "DB error", "DB insert summary error: {}", e)
+        })?;
+
+    tx.commit().await.map_err(|e| {
+        fail!(_cfg, "DB error", "DB commit error: {}", e)
+    })?;
+
+    Ok(inserted)
+}
+
+/// Inserts many summaries in a single transaction.
+///
+/// Each item runs inside its own savepoint so one bad event does not roll back
+/// the rest of the batch. Returns one `Result` per payload, in order.
+pub async fn insert_summaries_batch(
+    _cfg: &Config,
+    pool: &PgPool,
+    payloads: &[SummaryPayload],
+) -> Result<Vec<Result<(i32, DateTime<Utc>, bool), String>>, (StatusCode, &'static str)> {
+    let mut tx = pool.begin().await.map_err(|e| {
+        fail!(_cfg, "DB error", "DB begin transaction error: {}", e)
+    })?;
+
+    let mut results = Vec::with_capacity(payloads.len());
+    for payload in payloads {
+        let mut savepoint = tx.begin().await.map_err(|e| {
+            fail!(_cfg, "DB error", "DB savepoint error: {}", e)
+        })?;
+
+        match insert_summary_on_conn(&mut savepoint, payload, None).await {
+            Ok(inserted) => {
+                savepoint.commit().await.map_err(|e| {
+                    fail!(_cfg, "DB error", "DB savepoint release error: {}", e)
+                })?;
+                results.push(Ok(inserted));
+            }
+            Err(e) => {
+                savepoint.rollback().await.map_err(|e| {
+                    fail!(_cfg, "DB error", "DB savepoint rollback error: {}", e)
+                })?;
+                results.push(Err(e.to_string()));
+            }
+        }
+    }
+
+    tx.commit().await.map_err(|e| {
+        fail!(_cfg, "DB error", "DB commit error: {}", e)
+    })?;
+
+    Ok(results)
+}
+
+/// Shared insert logic for single and batch ingestion, run on an open transaction.
+async fn insert_summary_on_conn(
+    conn: &mut PgConnection,
+    payload: &SummaryPayload,
+    idempotency_key: Option<&str>,
+) -> Result<(i32, DateTime<Utc>, bool), sqlx::Error> {
+    // 1) Hold the ingest lock so aligned reports wait for this commit
+    lock_ingest_shared(&mut *conn).await?;
+
+    // 2) Determine created_at in UTC
     let created_at = Local::now().with_timezone(&Utc);
 
-    // 2) Insert into summaries dynamically
-    let row = sqlx::query(
+    // 3) Insert into summaries dynamically, skipping replays
+    let inserted = sqlx::query(
         r#"
         INSERT INTO summaries (
-          created_at, task, time, event, repo, plan, snapshot, error
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+          created_at, task, time, event, repo, plan, snapshot, error, idempotency_key
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT DO NOTHING
         RETURNING id, created_at
         "#
     )
     .bind(created_at)
     .bind(&payload.task)
-    .bind(&payload.time)
+    .bind(payload.time)
     .bind(&payload.event)
     .bind(&payload.repo)
     .bind(&payload.plan)
     .bind(&payload.snapshot)
     .bind(&payload.error)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| {
-        fail!(_cfg, "DB error", "DB insert summary error: {}", e)
-    })?;
+    .bind(idempotency_key)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let row = match inserted {
+        Some(row) => row,
+        None => {
+            // Replay: look up the original row instead
+            let existing = sqlx::query(
+                r#"
+                SELECT id, created_at
+                FROM summaries
+                WHERE ($1::TEXT IS NOT NULL AND idempotency_key = $1)
+                   OR (repo = $2 AND plan = $3 AND snapshot = $4 AND event = $5 AND time = $6)
+                ORDER BY id
+                LIMIT 1
+                "#
+            )
+            .bind(idempotency_key)
+            .bind(&payload.repo)
+            .bind(&payload.plan)
+            .bind(&payload.snapshot)
+            .bind(&payload.event)
+            .bind(payload.time)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            return Ok((existing.get("id"), existing.get("created_at"), false));
+        }
+    };
 
     // Extract with dynamic get
     let summary_id: i32 = row.get("id");
     let created: DateTime<Utc> = row.get("created_at");
 
-    // 3) Insert snapshot_stats if present
+    // 4) Insert snapshot_stats if present
     if let Some(stats) = &payload.snapshot_stats {
         sqlx::query(
             r#"
@@ -350,24 +870,20 @@ pub async fn insert_summary_with_stats(
         .bind(stats.total_bytes)
         .bind(stats.bytes_done)
         .bind(stats.current_files)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            fail!(_cfg, "DB error", "DB insert snapshot_stats error: {}", e)
-        })?;
+        .execute(&mut *conn)
+        .await?;
     }
 
-    Ok((summary_id, created))
+    Ok((summary_id, created, true))
 }
 
-/// Fetches all `CombinedStats` between two instants, or returns a `(StatusCode, &str)` error.
-pub async fn fetch_combined_stats(
-    _cfg: &Config,
-    pool: &PgPool,
-    start: DateTime<Utc>,
-    end: DateTime<Utc>,
-) -> Result<Vec<CombinedStats>, (StatusCode, &'static str)> {
-    sqlx::query_as::<_, CombinedStats>(r#"
+/// Page size of `GET /events` when no `limit` is given
+const EVENTS_DEFAULT_LIMIT: i64 = 100;
+/// Largest `limit` accepted by `GET /events`
+const EVENTS_MAX_LIMIT: i64 = 1000;
+
+/// Columns of a `CombinedStats` row: each summary with its snapshot stats, if any.
+pub const COMBINED_STATS_SELECT: &str = r#"
         SELECT
             s.id             AS summary_id,
             s.created_at,
@@ -403,8 +919,18 @@ pub async fn fetch_combined_stats(
             ss.current_files
         FROM summaries s
         LEFT JOIN snapshot_stats ss ON ss.summary_id = s.id
-        WHERE s.time BETWEEN $1 AND $2
-    "#)
+"#;
+
+/// Fetches all `CombinedStats` whose `by` time is between two instants, or returns a `(StatusCode, &str)` error.
+pub async fn fetch_combined_stats(
+    _cfg: &Config,
+    pool: &PgPool,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    by: EventTime,
+) -> Result<Vec<CombinedStats>, (StatusCode, &'static str)> {
+    let sql = format!("{} WHERE {} BETWEEN $1 AND $2", COMBINED_STATS_SELECT, by.column());
+    sqlx::query_as::<_, CombinedStats>(&sql)
     .bind(start)
     .bind(end)
     .fetch_all(pool)
@@ -414,20 +940,287 @@ pub async fn fetch_combined_stats(
     })
 }
 
-/// Fetches all of the aggregated counters between `start` and `end`.
+/// Fetches one page of `CombinedStats` matching `query`, newest first unless
+/// `sort=asc`, with the total number of matches.
+pub async fn fetch_events_page(
+    cfg: &Config,
+    pool: &PgPool,
+    query: &EventsQuery,
+) -> Result<EventsPage, (StatusCode, &'static str)> {
+    // 1) Validate paging
+    let limit = query.limit.unwrap_or(EVENTS_DEFAULT_LIMIT);
+    if !(1..=EVENTS_MAX_LIMIT).contains(&limit) {
+        return Err((StatusCode::BAD_REQUEST, "limit must be between 1 and 1000"));
+    }
+    let ascending = match query.sort.as_deref() {
+        None | Some("desc") => false,
+        Some("asc") => true,
+        Some(_) => return Err((StatusCode::BAD_REQUEST, "sort must be asc or desc")),
+    };
+    let cursor = match query.cursor.as_deref() {
+        Some(c) => Some(parse_event_cursor(c).ok_or((StatusCode::BAD_REQUEST, "Invalid cursor"))?),
+        None => None,
+    };
+    if cursor.is_some() && query.offset.is_some() {
+        return Err((StatusCode::BAD_REQUEST, "Use either cursor or offset, not both"));
+    }
+    let offset = query.offset.unwrap_or(0);
+    if offset < 0 {
+        return Err((StatusCode::BAD_REQUEST, "offset must not be negative"));
+    }
+    if let Some(status) = query.status.as_deref() {
+        if !matches!(status, "success" | "warning" | "error") {
+            return Err((StatusCode::BAD_REQUEST, "status must be success, warning or error"));
+        }
+    }
+
+    // 2) Count every match, ignoring paging
+    let mut count = QueryBuilder::<Postgres>::new(
+        "SELECT COUNT(*) FROM summaries s LEFT JOIN snapshot_stats ss ON ss.summary_id = s.id",
+    );
+    push_event_filters(&mut count, query);
+    let total: i64 = count
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await
+        .map_err(|e| fail!(cfg, "DB error", "DB query error in fetch_events_page: {}", e))?;
+
+    // 3) Fetch one more row than asked for, to know whether another page follows
+    let mut page = QueryBuilder::<Postgres>::new(COMBINED_STATS_SELECT);
+    push_event_filters(&mut page, query);
+    if let Some((time, id)) = cursor {
+        page.push(if ascending { " AND (s.time, s.id) > (" } else { " AND (s.time, s.id) < (" });
+        page.push_bind(time).push(", ").push_bind(id).push(")");
+    }
+    page.push(if ascending { " ORDER BY s.time ASC, s.id ASC" } else { " ORDER BY s.time DESC, s.id DESC" });
+    page.push(" LIMIT ").push_bind(limit + 1);
+    if cursor.is_none() {
+        page.push(" OFFSET ").push_bind(offset);
+    }
+    let mut events: Vec<CombinedStats> = page
+        .build_query_as()
+        .fetch_all(pool)
+        .await
+        .map_err(|e| fail!(cfg, "DB error", "DB query error in fetch_events_page: {}", e))?;
+
+    let has_more = events.len() as i64 > limit;
+    events.truncate(limit as usize);
+    let next_cursor = events
+        .last()
+        .filter(|_| has_more)
+        .map(|last| format!("{}_{}", last.time.timestamp_micros(), last.summary_id));
+
+    Ok(EventsPage {
+        total,
+        limit,
+        offset: cursor.is_none().then_some(offset),
+        next_cursor,
+        events,
+    })
+}
+
+/// Fetches the event `summary_id` and its related events, oldest first:
+/// the snapshot event it belongs to, the forget, prune and check events that
+/// followed that snapshot in the same repo and plan before the next one, and
+/// any other event with the same snapshot ID.
+pub async fn fetch_event_detail(
+    cfg: &Config,
+    pool: &PgPool,
+    summary_id: i32,
+) -> Result<Option<EventDetail>, (StatusCode, &'static str)> {
+    let db_err = |e: sqlx::Error| fail!(cfg, "DB error", "DB query error in fetch_event_detail: {}", e);
+
+    // 1) The event itself
+    let sql = format!("{} WHERE s.id = $1", COMBINED_STATS_SELECT);
+    let Some(event) = sqlx::query_as::<_, CombinedStats>(&sql)
+        .bind(summary_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_err)?
+    else {
+        return Ok(None);
+    };
+
+    // 2) The snapshot event it belongs to: itself, or the latest one before it
+    //    in the same repo and plan
+    let anchor: Option<(i32, DateTime<Utc>, String)> = if event.event.starts_with("snapshot ") {
+        Some((event.summary_id, event.time, event.snapshot.clone()))
+    } else {
+        sqlx::query_as(
+            r#"
+            SELECT id, time, snapshot
+              FROM summaries
+             WHERE repo = $1 AND plan = $2
+               AND event LIKE 'snapshot %'
+               AND (time, id) < ($3, $4)
+             ORDER BY time DESC, id DESC
+             LIMIT 1
+            "#,
+        )
+        .bind(&event.repo)
+        .bind(&event.plan)
+        .bind(event.time)
+        .bind(event.summary_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(db_err)?
+    };
+
+    // 3) Everything between that snapshot and the next one in the plan, plus
+    //    any event naming the same snapshot
+    let Some((anchor_id, anchor_time, anchor_snapshot)) = anchor else {
+        return Ok(Some(EventDetail { event, related: Vec::new() }));
+    };
+    let snapshot = Some(anchor_snapshot).filter(|s| !s.is_empty() && s != "null");
+    let sql = format!(
+        r#"{}
+        WHERE s.id <> $1
+          AND (
+                s.id = $2
+             OR ($6::TEXT IS NOT NULL AND s.snapshot = $6)
+             OR (s.repo = $3 AND s.plan = $4
+                 AND s.event NOT LIKE 'snapshot %'
+                 AND (s.time, s.id) > ($5, $2)
+                 AND NOT EXISTS (
+                     SELECT 1 FROM summaries n
+                      WHERE n.repo = s.repo AND n.plan = s.plan
+                        AND n.event LIKE 'snapshot %'
+                        AND (n.time, n.id) > ($5, $2)
+                        AND (n.time, n.id) < (s.time, s.id)))
+          )
+        ORDER BY s.time, s.id
+        "#,
+        COMBINED_STATS_SELECT
+    );
+    let related = sqlx::query_as::<_, CombinedStats>(&sql)
+        .bind(event.summary_id)
+        .bind(anchor_id)
+        .bind(&event.repo)
+        .bind(&event.plan)
+        .bind(anchor_time)
+        .bind(snapshot)
+        .fetch_all(pool)
+        .await
+        .map_err(db_err)?;
+
+    Ok(Some(EventDetail { event, related }))
+}
+
+/// Finds the snapshot event of a restic snapshot by its full ID or a prefix
+/// of at least 8 characters, such as the short ID in reports.
+pub async fn find_snapshot_event(
+    cfg: &Config,
+    pool: &PgPool,
+    snapshot_id: &str,
+) -> Result<Option<i32>, (StatusCode, &'static str)> {
+    if snapshot_id.len() < 8 || !snapshot_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err((StatusCode::BAD_REQUEST, "Snapshot ID must be at least 8 hex characters"));
+    }
+
+    let matches: Vec<(i32, String)> = sqlx::query_as(
+        r#"
+        SELECT s.id, s.snapshot
+          FROM summaries s
+         WHERE s.event LIKE 'snapshot %'
+           AND s.snapshot LIKE $1
+         ORDER BY s.time, s.id
+        "#,
+    )
+    .bind(format!("{}%", snapshot_id.to_lowercase()))
+    .fetch_all(pool)
+    .await
+    .map_err(|e| fail!(cfg, "DB error", "DB query error in find_snapshot_event: {}", e))?;
+
+    // A short ID may match more than one snapshot
+    if matches.iter().any(|(_, id)| *id != matches[0].1) {
+        return Err((StatusCode::CONFLICT, "Snapshot ID matches more than one snapshot"));
+    }
+    Ok(matches.first().map(|(id, _)| *id))
+}
+
+/// Appends the `WHERE` clause for every filter set in `query`.
+fn push_event_filters(qb: &mut QueryBuilder<'_, Postgres>, query: &EventsQuery) {
+    qb.push(" WHERE TRUE");
+    if let Some(start) = query.start {
+        qb.push(" AND s.time >= ").push_bind(start);
+    }
+    if let Some(end) = query.end {
+        qb.push(" AND s.time <= ").push_bind(end);
+    }
+    if let Some(repo) = query.repo.clone() {
+        qb.push(" AND s.repo = ").push_bind(repo);
+    }
+    if let Some(plan) = query.plan.clone() {
+        qb.push(" AND s.plan = ").push_bind(plan);
+    }
+    // Either a full event such as `snapshot error`, or just its type
+    if let Some(event) = query.event.clone() {
+        if event.contains(' ') {
+            qb.push(" AND s.event = ").push_bind(event);
+        } else {
+            qb.push(" AND s.event LIKE ").push_bind(format!("{} %", escape_like(&event)));
+        }
+    }
+    if let Some(status) = query.status.as_deref() {
+        qb.push(" AND s.event LIKE ").push_bind(format!("% {}", status));
+    }
+    if let Some(text) = query.q.as_deref().filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", escape_like(text));
+        qb.push(" AND (s.error ILIKE ").push_bind(pattern.clone());
+        qb.push(" OR ss.error ILIKE ").push_bind(pattern).push(")");
+    }
+}
+
+/// Parses a `next_cursor` value: the row's time in microseconds and its id.
+fn parse_event_cursor(cursor: &str) -> Option<(DateTime<Utc>, i32)> {
+    let (micros, id) = cursor.split_once('_')?;
+    let time = DateTime::from_timestamp_micros(micros.parse().ok()?)?;
+    Some((time, id.parse().ok()?))
+}
+
+/// Escapes `%`, `_` and `\` so `text` matches literally in a `LIKE` pattern.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Fetches all of the aggregated counters for events whose `by` time is
+/// between `start` and `end`.
 pub async fn fetch_event_totals(
     _cfg: &Config,
     pool: &PgPool,
     start: DateTime<Utc>,
     end: DateTime<Utc>,
+    by: EventTime,
 ) -> Result<EventTotals, (StatusCode, &'static str)> {
     // We LEFT JOIN snapshot_stats so that events without stats still count.
-    let row: EventTotals = sqlx::query_as::<_, EventTotals>(
+    let sql = format!(
         r#"
         SELECT
           $1::timestamptz  AS start_date,
           $2::timestamptz  AS end_date,
+{}
+        FROM summaries s
+        LEFT JOIN snapshot_stats ss ON ss.summary_id = s.id
+        WHERE {} BETWEEN $1 AND $2
+        "#,
+        EVENT_TOTALS_COLUMNS,
+        by.column()
+    );
+    let row: EventTotals = sqlx::query_as::<_, EventTotals>(&sql)
+    .bind(start)
+    .bind(end)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| {
+        fail!(_cfg, "DB error", "DB aggregation error: {}", e)
+    })?;
+
+    Ok(row)
+}
 
+/// Aggregate columns shared by every event totals query. Expects `summaries s`
+/// LEFT JOINed with `snapshot_stats ss`.
+const EVENT_TOTALS_COLUMNS: &str = r#"
           COALESCE(COUNT(*)                                                           , 0)::BIGINT AS total_events,
           COALESCE(SUM(CASE WHEN s.event ILIKE '%snapshot success%' THEN 1 ELSE 0 END), 0)::BIGINT AS total_snapshot_success,
           COALESCE(SUM(CASE WHEN s.event ILIKE '%snapshot warning%' THEN 1 ELSE 0 END), 0)::BIGINT AS total_snapshot_warning,
@@ -441,7 +1234,7 @@ pub async fn fetch_event_totals(
           COALESCE(SUM(CASE WHEN s.event ILIKE '%check success%'    THEN 1 ELSE 0 END), 0)::BIGINT AS total_check_success,
           COALESCE(SUM(CASE WHEN s.event ILIKE '%check warning%'    THEN 1 ELSE 0 END), 0)::BIGINT AS total_check_warning,
           COALESCE(SUM(CASE WHEN s.event ILIKE '%check error%'      THEN 1 ELSE 0 END), 0)::BIGINT AS total_check_error,
-    
+
           COALESCE(SUM(ss.files_new)       , 0)::BIGINT AS total_files_new,
           COALESCE(SUM(ss.files_changed)   , 0)::BIGINT AS total_files_changed,
           COALESCE(SUM(ss.files_unmodified), 0)::BIGINT AS total_files_unmodified,
@@ -456,21 +1249,48 @@ pub async fn fetch_event_totals(
           COALESCE(SUM(ss.total_files_processed) , 0)::BIGINT AS total_files_processed,
           COALESCE(SUM(ss.total_bytes_processed) , 0)::BIGINT AS total_bytes_processed,
           COALESCE(SUM(ss.total_duration)        , 0)::BIGINT AS total_duration
-    
+"#;
+
+/// Fetches the aggregated counters between `start` and `end` for each repo or
+/// plan that has events in the window, ordered by name.
+pub async fn fetch_event_totals_grouped(
+    _cfg: &Config,
+    pool: &PgPool,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    group: TotalsGroup,
+    by: EventTime,
+) -> Result<Vec<NamedEventTotals>, (StatusCode, &'static str)> {
+    let column = match group {
+        TotalsGroup::Repo => "s.repo",
+        TotalsGroup::Plan => "s.plan",
+    };
+    let sql = format!(
+        r#"
+        SELECT
+          {0}              AS name,
+          $1::timestamptz  AS start_date,
+          $2::timestamptz  AS end_date,
+{1}
         FROM summaries s
         LEFT JOIN snapshot_stats ss ON ss.summary_id = s.id
-        WHERE s.time BETWEEN $1 AND $2
-        "#
-    )
-    .bind(start)
-    .bind(end)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| {
-        fail!(_cfg, "DB error", "DB aggregation error: {}", e)
-    })?;
+        WHERE {2} BETWEEN $1 AND $2
+        GROUP BY {0}
+        ORDER BY {0}
+        "#,
+        column,
+        EVENT_TOTALS_COLUMNS,
+        by.column()
+    );
 
-    Ok(row)
+    sqlx::query_as::<_, NamedEventTotals>(&sql)
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| {
+            fail!(_cfg, "DB error", "DB grouped aggregation error: {}", e)
+        })
 }
 
 /// Build a report of event totals for current, previous day/week/month.
@@ -479,9 +1299,10 @@ pub async fn load_event_totals_report(
     pool: &PgPool,
     start: DateTime<Utc>,
     end:   DateTime<Utc>,
+    by:    EventTime,
 ) -> Result<EventTotalsReport, (StatusCode, &'static str)> {
     // 1) Current window
-    let current = fetch_event_totals(_cfg, pool, start, end).await?;
+    let current = fetch_event_totals(_cfg, pool, start, end, by).await?;
 
     // 2) Compute cutoffs
     let day_start   = start   - Duration::days(1);
@@ -492,9 +1313,9 @@ pub async fn load_event_totals_report(
     let month_end   = end     - Duration::days(30);
 
     // 3) Previous windows
-    let previous_day   = fetch_event_totals(_cfg, pool, day_start, day_end).await.ok();
-    let previous_week  = fetch_event_totals(_cfg, pool, week_start, week_end).await.ok();
-    let previous_month = fetch_event_totals(_cfg, pool, month_start, month_end).await.ok();
+    let previous_day   = fetch_event_totals(_cfg, pool, day_start, day_end, by).await.ok();
+    let previous_week  = fetch_event_totals(_cfg, pool, week_start, week_end, by).await.ok();
+    let previous_month = fetch_event_totals(_cfg, pool, month_start, month_end, by).await.ok();
 
     // 4) Assemble report
     Ok(EventTotalsReport {
@@ -505,6 +1326,61 @@ pub async fn load_event_totals_report(
     })
 }
 
+/// Build per-repo and per-plan event totals for current, previous day/week/month.
+pub async fn load_totals_breakdown(
+    _cfg: &Config,
+    pool: &PgPool,
+    start: DateTime<Utc>,
+    end:   DateTime<Utc>,
+    by:    EventTime,
+) -> Result<TotalsBreakdown, (StatusCode, &'static str)> {
+    Ok(TotalsBreakdown {
+        by_repo: load_group_totals_report(_cfg, pool, start, end, TotalsGroup::Repo, by).await?,
+        by_plan: load_group_totals_report(_cfg, pool, start, end, TotalsGroup::Plan, by).await?,
+    })
+}
+
+/// Grouped totals for the current window, each matched with the same repo or
+/// plan in the previous day/week/month windows.
+async fn load_group_totals_report(
+    _cfg: &Config,
+    pool: &PgPool,
+    start: DateTime<Utc>,
+    end:   DateTime<Utc>,
+    group: TotalsGroup,
+    by:    EventTime,
+) -> Result<Vec<GroupTotalsReport>, (StatusCode, &'static str)> {
+    // 1) Current window decides which repos or plans are listed
+    let current = fetch_event_totals_grouped(_cfg, pool, start, end, group, by).await?;
+
+    // 2) Previous windows, keyed by name
+    let previous = |offset: Duration| async move {
+        fetch_event_totals_grouped(_cfg, pool, start - offset, end - offset, group, by)
+            .await
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|r| (r.name, r.totals))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default()
+    };
+    let mut previous_day   = previous(Duration::days(1)).await;
+    let mut previous_week  = previous(Duration::weeks(1)).await;
+    let mut previous_month = previous(Duration::days(30)).await;
+
+    // 3) Assemble one report per repo or plan
+    Ok(current
+        .into_iter()
+        .map(|row| GroupTotalsReport {
+            previous_day:   previous_day.remove(&row.name),
+            previous_week:  previous_week.remove(&row.name),
+            previous_month: previous_month.remove(&row.name),
+            name:           row.name,
+            current:        row.totals,
+        })
+        .collect())
+}
+
 /// Stats all mounts, inserts into the DB, and returns the summaries.
 pub async fn load_and_insert_storage_stats(
     pool: &PgPool,
@@ -592,6 +1468,15 @@ pub async fn load_and_insert_storage_stats(
             )
         })?;
 
+        // alert if it crossed a usage threshold
+        tokio::spawn(check_storage_thresholds(
+            cfg.clone(),
+            pool.clone(),
+            mount.clone(),
+            used_bytes,
+            total_bytes,
+        ));
+
         reports.push(StorageReport {
             location:   path.clone(),
             nickname,
@@ -664,7 +1549,7 @@ pub async fn load_storage_stats(
     // 3) Build the response vector
     let mut out = Vec::with_capacity(mounts.len());
     for mount in mounts {
-        if let Some(cur) = current_rows.iter().find(|r| &r.storage_location == &mount.path) {
+        if let Some(cur) = current_rows.iter().find(|r| r.storage_location == mount.path) {
             // pack current
             let make_period = |r: &DbStorageRow| PeriodStats {
                 used_bytes:   r.storage_used_bytes,
@@ -702,13 +1587,25 @@ pub async fn load_storage_stats(
                     })?;
             }
 
-            let previous_day   = priors[0].as_ref().map(|r| make_period(r));
-            let previous_week  = priors[1].as_ref().map(|r| make_period(r));
-            let previous_month = priors[2].as_ref().map(|r| make_period(r));
+            let previous_day   = priors[0].as_ref().map(&make_period);
+            let previous_week  = priors[1].as_ref().map(&make_period);
+            let previous_month = priors[2].as_ref().map(make_period);
 
             // use config nickname preferentially
             let nickname = mount.nickname.clone().or(cur.storage_nickname.clone());
 
+            // project growth up to the latest sample
+            let forecast = forecast_storage(
+                pool,
+                cfg,
+                &mount.path,
+                cur.storage_used_bytes,
+                cur.storage_total_bytes,
+                cur.time_added,
+            )
+            .await
+            .map_err(|e| fail!(cfg, "DB error", "DB forecast for {}: {}", mount.path, e))?;
+
             out.push(CurrentStorageStats {
                 location:       mount.path.clone(),
                 nickname,
@@ -716,6 +1613,13 @@ pub async fn load_storage_stats(
                 previous_day,
                 previous_week,
                 previous_month,
+                usage_level: usage_level(
+                    mount,
+                    cur.storage_used_bytes.max(0) as u64,
+                    cur.storage_total_bytes.max(0) as u64,
+                )
+                .as_str(),
+                forecast,
             });
         }
     }
@@ -791,7 +1695,7 @@ pub async fn load_storage_stats_at(
     for mount in mounts {
         if let Some(cur) = current_rows
             .iter()
-            .find(|r| &r.storage_location == &mount.path)
+            .find(|r| r.storage_location == mount.path)
         {
             let make_period = |r: &DbStorageRow| PeriodStats {
                 used_bytes:   r.storage_used_bytes,
@@ -831,13 +1735,25 @@ pub async fn load_storage_stats_at(
                     })?;
             }
 
-            let previous_day   = priors[0].as_ref().map(|r| make_period(r));
-            let previous_week  = priors[1].as_ref().map(|r| make_period(r));
-            let previous_month = priors[2].as_ref().map(|r| make_period(r));
+            let previous_day   = priors[0].as_ref().map(&make_period);
+            let previous_week  = priors[1].as_ref().map(&make_period);
+            let previous_month = priors[2].as_ref().map(make_period);
 
             // pick nickname
             let nickname = mount.nickname.clone().or(cur.storage_nickname.clone());
 
+            // project growth up to the latest sample before `end`
+            let forecast = forecast_storage(
+                pool,
+                cfg,
+                &mount.path,
+                cur.storage_used_bytes,
+                cur.storage_total_bytes,
+                cur.time_added,
+            )
+            .await
+            .map_err(|e| fail!(cfg, "DB error", "forecast for {}: {}", mount.path, e))?;
+
             out.push(CurrentStorageStats {
                 location:       mount.path.clone(),
                 nickname,
@@ -845,6 +1761,13 @@ pub async fn load_storage_stats_at(
                 previous_day,
                 previous_week,
                 previous_month,
+                usage_level: usage_level(
+                    mount,
+                    cur.storage_used_bytes.max(0) as u64,
+                    cur.storage_total_bytes.max(0) as u64,
+                )
+                .as_str(),
+                forecast,
             });
         }
     }
@@ -852,9 +1775,9 @@ pub async fn load_storage_stats_at(
     Ok(out)
 }
 
-///
-/// HELPER METHODS
-/// 
+//
+// HELPER METHODS
+//
 
 /// Validates the API key provided in the headers.
 pub fn validate_api_key_with_ip(
@@ -903,4 +1826,67 @@ pub fn status_emoji_for_event_totals(et: &EventTotals) -> &'static str {
             "✅"
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{init_db, wait_for_ingests};
+
+    /// Needs a scratch Postgres in `TEST_DATABASE_URL`; skipped without one.
+    async fn test_pool() -> Option<PgPool> {
+        let url = std::env::var("TEST_DATABASE_URL").ok().filter(|s| !s.trim().is_empty())?;
+        Some(init_db(&url, true).await.expect("test database should migrate"))
+    }
+
+    #[tokio::test]
+    async fn aligned_report_waits_for_open_ingest() {
+        let Some(pool) = test_pool().await else {
+            eprintln!("TEST_DATABASE_URL not set, skipping");
+            return;
+        };
+        let payload = SummaryPayload {
+            task:           "backup".into(),
+            time:           Utc::now(),
+            event:          "snapshot".into(),
+            repo:           "ingest-lock-test".into(),
+            plan:           "plan".into(),
+            snapshot:       format!("{}", Utc::now().timestamp_nanos_opt().unwrap()),
+            error:          None,
+            snapshot_stats: None,
+        };
+
+        // 1) An ingest stamps its event but hasn't committed yet
+        let start = Utc::now();
+        let mut ingest = pool.begin().await.unwrap();
+        let (id, _, _) = insert_summary_on_conn(&mut ingest, &payload, None).await.unwrap();
+
+        // 2) A report for a window covering it fires, and must wait
+        let end = Utc::now();
+        let report = tokio::spawn({
+            let pool = pool.clone();
+            async move { wait_for_ingests(&pool).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(!report.is_finished());
+
+        // 3) Once the ingest commits, the report sees the event in its window
+        ingest.commit().await.unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), report)
+            .await
+            .expect("report should stop waiting after the commit")
+            .unwrap()
+            .unwrap();
+        let seen: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM summaries WHERE id = $1 AND created_at BETWEEN $2 AND $3",
+        )
+        .bind(id)
+        .bind(start)
+        .bind(end)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(seen, 1);
+
+        sqlx::query("DELETE FROM summaries WHERE id = $1").bind(id).execute(&pool).await.unwrap();
+    }
+}
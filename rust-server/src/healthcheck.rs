@@ -1,9 +1,13 @@
 use reqwest::Client;
 use tracing::{info, warn, error};
 
+use crate::metrics;
+
 /// Healthcheck States
 #[derive(Debug, Clone, Copy)]
 pub enum HealthStatus {
+    /// Only pinged through the `start!` macro
+    #[allow(dead_code)]
     Start,
     Success,
     Fail,
@@ -51,12 +55,14 @@ pub fn ping_healthcheck(
                     info!("Healthcheck {} ping to {} succeeded", format!("{:?}", status), url_clone);
                 }
                 Ok(resp) => {
+                    metrics::add(&metrics::HEALTHCHECK_PING_FAILURES, 1);
                     warn!(
                         "Healthcheck {:?} ping to {} returned {}",
                         status, url_clone, resp.status()
                     );
                 }
                 Err(e) => {
+                    metrics::add(&metrics::HEALTHCHECK_PING_FAILURES, 1);
                     error!("Healthcheck {:?} ping to {} failed: {}", status, url_clone, e);
                 }
             }
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use sqlx::PgPool;
+use std::{future::Future, str::FromStr};
+
+use crate::{
+    config::Config,
+    models::{JobRun, JobStatus},
+};
+
+/// Number of runs listed per job by `GET /jobs`
+const RECENT_RUNS: i64 = 10;
+
+/// A job run by the scheduler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobKind {
+    /// A scheduled report, by name
+    Report(String),
+    StorageUpdate,
+    Prune,
+    MissedBackupCheck,
+}
+
+impl JobKind {
+    /// Stable name, as recorded in `job_runs`, e.g. `report:weekly`
+    pub fn name(&self) -> String {
+        match self {
+            JobKind::Report(name)      => format!("report:{}", name),
+            JobKind::StorageUpdate     => "storage_update".into(),
+            JobKind::Prune             => "prune".into(),
+            JobKind::MissedBackupCheck => "missed_backup_check".into(),
+        }
+    }
+
+    /// Setting the job's cron expression comes from, for messages
+    pub fn setting(&self) -> String {
+        match self {
+            JobKind::Report(name) if name == "default" => "EMAIL_FREQUENCY".into(),
+            JobKind::Report(name)      => format!("the frequency of report '{}'", name),
+            JobKind::StorageUpdate     => "STORAGE_FREQUENCY".into(),
+            JobKind::Prune             => "PRUNE_FREQUENCY".into(),
+            JobKind::MissedBackupCheck => "MISSED_CHECK_FREQUENCY".into(),
+        }
+    }
+
+    /// Every enabled job with its cron expression
+    pub fn enabled(cfg: &Config) -> Vec<(JobKind, String)> {
+        let mut jobs: Vec<(JobKind, String)> = cfg
+            .reports
+            .iter()
+            .map(|r| (JobKind::Report(r.name.clone()), r.frequency.clone()))
+            .collect();
+        jobs.push((JobKind::StorageUpdate, cfg.storage_frequency.clone()));
+        if cfg.retention.is_enabled() {
+            jobs.push((JobKind::Prune, cfg.retention.prune_frequency.clone()));
+        }
+        if !cfg.missed.expected.is_empty() {
+            jobs.push((JobKind::MissedBackupCheck, cfg.missed.check_frequency.clone()));
+        }
+        jobs
+    }
+}
+
+/// Awaits `work` as one run of `job`, recording when it started and finished
+/// and its outcome. Recording failures are logged only.
+pub async fn run_recorded<Fut>(pool: &PgPool, job: &JobKind, work: Fut)
+where
+    Fut: Future<Output = Result<(), String>>,
+{
+    // 1) Record the start
+    let id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO job_runs (job, outcome) VALUES ($1, 'running') RETURNING id",
+    )
+    .bind(job.name())
+    .fetch_one(pool)
+    .await
+    .map_err(|e| tracing::error!("Recording start of {} run failed: {}", job.name(), e))
+    .ok();
+
+    // 2) Run
+    let result = work.await;
+
+    // 3) Record the outcome
+    let Some(id) = id else {
+        return;
+    };
+    let (outcome, error) = match &result {
+        Ok(())   => ("success", None),
+        Err(msg) => ("failure", Some(msg.as_str())),
+    };
+    let recorded = sqlx::query(
+        "UPDATE job_runs SET finished_at = now(), outcome = $2, error = $3 WHERE id = $1",
+    )
+    .bind(id)
+    .bind(outcome)
+    .bind(error)
+    .execute(pool)
+    .await;
+    if let Err(e) = recorded {
+        tracing::error!("Recording outcome of {} run failed: {}", job.name(), e);
+    }
+}
+
+/// Marks runs that were still running before `before` as interrupted, as the
+/// process that ran them has stopped.
+pub async fn mark_interrupted_runs(pool: &PgPool, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+    let done = sqlx::query(
+        r#"
+        UPDATE job_runs
+           SET outcome = 'interrupted', finished_at = now()
+         WHERE outcome = 'running'
+           AND started_at < $1
+        "#,
+    )
+    .bind(before)
+    .execute(pool)
+    .await?;
+
+    Ok(done.rows_affected())
+}
+
+/// Every enabled job with its schedule, next fire time and latest runs.
+pub async fn job_statuses(pool: &PgPool, cfg: &Config) -> Result<Vec<JobStatus>, sqlx::Error> {
+    let mut statuses = Vec::new();
+
+    for (job, expr) in JobKind::enabled(cfg) {
+        let recent_runs = sqlx::query_as::<_, JobRun>(
+            r#"
+            SELECT id, started_at, finished_at, outcome, error
+              FROM job_runs
+             WHERE job = $1
+             ORDER BY started_at DESC
+             LIMIT $2
+            "#,
+        )
+        .bind(job.name())
+        .bind(RECENT_RUNS)
+        .fetch_all(pool)
+        .await?;
+
+        statuses.push(JobStatus {
+            name:     job.name(),
+            next_run: Schedule::from_str(&expr)
+                .ok()
+                .and_then(|s| s.upcoming(Utc).next()),
+            schedule: expr,
+            recent_runs,
+        });
+    }
+
+    Ok(statuses)
+}
@@ -1,16 +1,31 @@
+mod alerts;
+mod charts;
 mod config;
 mod db;
 mod email;
+mod export;
+mod forecast;
 mod handlers;
 mod healthcheck;
 mod html_report;
+mod jobs;
+mod metrics;
+mod missed;
 mod models;
+mod notify;
+mod probes;
+mod reports;
+mod retention;
 mod scheduler;
+mod templates;
+mod text_report;
+mod thresholds;
 mod utils;
 
 use std::net::SocketAddr;
 
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{get, post},
     Router, serve,
 };
@@ -18,22 +33,34 @@ use tokio::net::TcpListener;
 use tracing_subscriber::{fmt, EnvFilter};
 
 use config::Config;
-use db::init_db;
+use db::{init_db, latest_version, migrate, schema_version};
 use handlers::{
     add_event_handler,
+    add_events_handler,
+    export_events_handler,
+    export_storage_handler,
     generate_and_send_email_report,
     get_events_and_storage_stats_handler,
+    get_event_handler,
+    get_events_handler,
+    get_events_in_range_breakdown_handler,
     get_events_in_range_handler,
     get_events_in_range_totals_handler,
+    get_jobs_handler,
     get_latest_storage_stats_handler,
+    get_metrics_handler,
+    get_snapshot_handler,
     get_storage_stats_handler,
+    healthz_handler,
+    readyz_handler,
+    send_named_report_handler,
     send_test_email_handler,
     update_storage_statistics_handler,
 };
-use scheduler::{
-    spawn_email_report_cron,
-    spawn_storage_update_cron,
-};
+use scheduler::start_scheduler;
+
+/// Maximum request body size accepted by the `/add-events` backfill endpoint
+const BATCH_BODY_LIMIT: usize = 64 * 1024 * 1024;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -43,15 +70,34 @@ async fn main() -> anyhow::Result<()> {
         .with_ansi(false)
         .init();
 
-    // Load config & DB
+    // Load config
     let cfg = Config::from_env()?;
-    let pool = init_db(&cfg.database_url).await?;
 
-    // Spawn the cron scheduler in a background task
-    spawn_email_report_cron(cfg.clone()).await;
+    // `rust-server migrate` applies pending schema migrations and exits
+    if std::env::args().nth(1).as_deref() == Some("migrate") {
+        let pool = sqlx::PgPool::connect(&cfg.database_url).await?;
+        let current = schema_version(&pool).await?;
+        if current > latest_version() {
+            anyhow::bail!(
+                "Database schema is at version {} but this build only knows up to version {}",
+                current,
+                latest_version()
+            );
+        }
+        let applied = migrate(&pool).await?;
+        if applied.is_empty() {
+            tracing::info!("Schema already at version {}, nothing to migrate", current);
+        } else {
+            tracing::info!("Migrated schema from version {} to {}", current, latest_version());
+        }
+        return Ok(());
+    }
 
-    // Kick off storage update scheduler
-    spawn_storage_update_cron(cfg.clone()).await;
+    // Connect to the DB, checking the schema version
+    let pool = init_db(&cfg.database_url, cfg.auto_migrate).await?;
+
+    // Start the reporting, storage, pruning, and missed-backup jobs
+    start_scheduler(cfg.clone(), pool.clone())?;
 
     // Bind TCP listener
     let listener = TcpListener::bind(cfg.listen_addr).await?;
@@ -62,6 +108,27 @@ async fn main() -> anyhow::Result<()> {
         .route(
             "/add-event",
             post(add_event_handler))
+        .route(
+            "/add-events",
+            post(add_events_handler)
+                .layer(DefaultBodyLimit::max(BATCH_BODY_LIMIT)),
+        )
+        .route(
+            "/events",
+            get(get_events_handler),
+        )
+        .route(
+            "/events/:summary_id",
+            get(get_event_handler),
+        )
+        .route(
+            "/export/events",
+            get(export_events_handler),
+        )
+        .route(
+            "/export/storage",
+            get(export_storage_handler),
+        )
         .route(
             "/generate-and-send-email-report",
             post(generate_and_send_email_report),
@@ -73,6 +140,10 @@ async fn main() -> anyhow::Result<()> {
         .route(
             "/get-events-in-range",
             post(get_events_in_range_handler))
+        .route(
+            "/get-events-in-range-breakdown",
+            post(get_events_in_range_breakdown_handler),
+        )
         .route(
             "/get-events-in-range-totals",
             post(get_events_in_range_totals_handler),
@@ -85,6 +156,30 @@ async fn main() -> anyhow::Result<()> {
             "/get-storage-stats",
             post(get_storage_stats_handler),
         )
+        .route(
+            "/healthz",
+            get(healthz_handler),
+        )
+        .route(
+            "/jobs",
+            get(get_jobs_handler),
+        )
+        .route(
+            "/metrics",
+            get(get_metrics_handler),
+        )
+        .route(
+            "/readyz",
+            get(readyz_handler),
+        )
+        .route(
+            "/reports/:name/send",
+            post(send_named_report_handler),
+        )
+        .route(
+            "/snapshots/:snapshot_id",
+            get(get_snapshot_handler),
+        )
         .route(
             "/send-test-email",
             get(send_test_email_handler))
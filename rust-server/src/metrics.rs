@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+use crate::config::Config;
+
+/// Prefix of every metric name
+const PREFIX: &str = "backrest_reporter";
+
+/// How long the database gauges are reused between scrapes
+const CACHE_TTL: Duration = Duration::from_secs(15);
+/// Database gauges of the last scrape that queried, and when they were rendered
+static CACHE: Mutex<Option<(Instant, String)>> = Mutex::const_new(None);
+
+/// Events stored by `/add-event` and `/add-events` since startup, duplicates excluded
+pub static EVENTS_INGESTED: AtomicU64 = AtomicU64::new(0);
+/// Emails the SMTP server accepted since startup
+pub static EMAILS_SENT: AtomicU64 = AtomicU64::new(0);
+/// Emails that failed to send since startup
+pub static EMAILS_FAILED: AtomicU64 = AtomicU64::new(0);
+/// Healthcheck pings that failed or were rejected since startup
+pub static HEALTHCHECK_PING_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// Adds `n` to a process counter.
+pub fn add(counter: &AtomicU64, n: u64) {
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+/// Renders every metric in the Prometheus text exposition format.
+///
+/// The database gauges are reused for `CACHE_TTL` so frequent scrapes don't
+/// each aggregate the whole `summaries` table; process counters are always
+/// current.
+pub async fn render_metrics(pool: &PgPool, cfg: &Config) -> Result<String, sqlx::Error> {
+    // 1) Database gauges, rendered again once the cached ones are stale.
+    // Concurrent scrapes wait on the lock instead of querying in parallel.
+    let mut cache = CACHE.lock().await;
+    let mut out = match cache.as_ref() {
+        Some((rendered_at, gauges)) if rendered_at.elapsed() < CACHE_TTL => gauges.clone(),
+        _ => {
+            let gauges = render_db_metrics(pool, cfg).await?;
+            *cache = Some((Instant::now(), gauges.clone()));
+            gauges
+        }
+    };
+    drop(cache);
+
+    // 2) Process counters
+    let counters = [
+        ("events_ingested_total", "Events stored since startup, duplicates excluded", &EVENTS_INGESTED),
+        ("emails_sent_total", "Emails sent since startup", &EMAILS_SENT),
+        ("emails_failed_total", "Emails that failed to send since startup", &EMAILS_FAILED),
+        ("healthcheck_ping_failures_total", "Healthcheck pings that failed since startup", &HEALTHCHECK_PING_FAILURES),
+    ];
+    for (name, help, counter) in counters {
+        family(&mut out, name, "counter", help);
+        sample(&mut out, name, &[], counter.load(Ordering::Relaxed) as f64);
+    }
+
+    Ok(out)
+}
+
+/// Renders the gauges read from the database.
+///
+/// Per repo and plan, the gauges come from the stored events, so they cover
+/// what retention has kept. Storage gauges are the latest sample of each
+/// configured mount.
+async fn render_db_metrics(pool: &PgPool, cfg: &Config) -> Result<String, sqlx::Error> {
+    let mut out = String::new();
+
+    // 1) Stored events per repo, plan and event type
+    let counts = sqlx::query_as::<_, (String, String, String, i64)>(
+        r#"
+        SELECT repo, plan, event, COUNT(*)
+          FROM summaries
+         GROUP BY repo, plan, event
+         ORDER BY repo, plan, event
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    family(&mut out, "events", "gauge", "Stored events by repo, plan and event type");
+    for (repo, plan, event, count) in &counts {
+        sample(&mut out, "events", &[("repo", repo), ("plan", plan), ("event", event)], *count as f64);
+    }
+
+    // 2) Latest outcome and latest successful snapshot per repo and plan
+    let latest = sqlx::query_as::<_, (String, String, String, DateTime<Utc>, Option<DateTime<Utc>>)>(
+        r#"
+        SELECT DISTINCT ON (repo, plan)
+               repo, plan, event, time,
+               MAX(time) FILTER (WHERE event = 'snapshot success') OVER (PARTITION BY repo, plan)
+          FROM summaries
+         WHERE event LIKE '% success' OR event LIKE '% warning' OR event LIKE '% error'
+         ORDER BY repo, plan, time DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    family(
+        &mut out,
+        "last_event_status",
+        "gauge",
+        "Outcome of the latest event: 0 success, 1 warning, 2 error",
+    );
+    for (repo, plan, event, _, _) in &latest {
+        let status = if event.ends_with("error") {
+            2.0
+        } else if event.ends_with("warning") {
+            1.0
+        } else {
+            0.0
+        };
+        sample(&mut out, "last_event_status", &[("repo", repo), ("plan", plan)], status);
+    }
+    family(&mut out, "last_event_timestamp_seconds", "gauge", "Time of the latest event");
+    for (repo, plan, _, time, _) in &latest {
+        sample(&mut out, "last_event_timestamp_seconds", &[("repo", repo), ("plan", plan)], seconds(*time));
+    }
+    family(
+        &mut out,
+        "last_success_timestamp_seconds",
+        "gauge",
+        "Time of the latest successful snapshot",
+    );
+    for (repo, plan, _, _, last_success) in &latest {
+        if let Some(t) = last_success {
+            sample(&mut out, "last_success_timestamp_seconds", &[("repo", repo), ("plan", plan)], seconds(*t));
+        }
+    }
+
+    // 3) Statistics of the latest snapshot per repo and plan
+    let snapshots = sqlx::query_as::<_, (String, String, Option<i64>, Option<f64>)>(
+        r#"
+        SELECT DISTINCT ON (s.repo, s.plan)
+               s.repo, s.plan, ss.data_added, ss.total_duration
+          FROM summaries s
+          JOIN snapshot_stats ss ON ss.summary_id = s.id
+         ORDER BY s.repo, s.plan, s.time DESC
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    family(&mut out, "last_data_added_bytes", "gauge", "Data added by the latest snapshot");
+    for (repo, plan, data_added, _) in &snapshots {
+        if let Some(bytes) = data_added {
+            sample(&mut out, "last_data_added_bytes", &[("repo", repo), ("plan", plan)], *bytes as f64);
+        }
+    }
+    family(&mut out, "last_duration_seconds", "gauge", "Duration of the latest snapshot");
+    for (repo, plan, _, duration) in &snapshots {
+        if let Some(secs) = duration {
+            sample(&mut out, "last_duration_seconds", &[("repo", repo), ("plan", plan)], *secs);
+        }
+    }
+
+    // 4) Latest sample of each configured storage mount
+    let paths: Vec<String> = cfg.storage_mounts.iter().map(|m| m.path.clone()).collect();
+    let storage = sqlx::query_as::<_, (String, Option<String>, Option<i64>, Option<i64>)>(
+        r#"
+        SELECT DISTINCT ON (storage_location)
+               storage_location, storage_nickname, storage_used_bytes, storage_total_bytes
+          FROM storage
+         WHERE storage_location = ANY($1)
+         ORDER BY storage_location, time_added DESC
+        "#,
+    )
+    .bind(&paths)
+    .fetch_all(pool)
+    .await?;
+    family(&mut out, "storage_used_bytes", "gauge", "Used bytes of the storage mount");
+    for (location, nickname, used, _) in &storage {
+        if let Some(used) = used {
+            let nickname = nickname.as_deref().unwrap_or_default();
+            sample(&mut out, "storage_used_bytes", &[("location", location), ("nickname", nickname)], *used as f64);
+        }
+    }
+    family(&mut out, "storage_total_bytes", "gauge", "Total bytes of the storage mount");
+    for (location, nickname, _, total) in &storage {
+        if let Some(total) = total {
+            let nickname = nickname.as_deref().unwrap_or_default();
+            sample(&mut out, "storage_total_bytes", &[("location", location), ("nickname", nickname)], *total as f64);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Writes the `# HELP` and `# TYPE` lines of a metric.
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+}
+
+/// Writes one sample, e.g. `backrest_reporter_events{repo="r",plan="p"} 3`.
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    let _ = write!(out, "{}_{}", PREFIX, name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/// Escapes a label value: backslash, double quote and newline.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Unix time in seconds, with fractions.
+fn seconds(t: DateTime<Utc>) -> f64 {
+    t.timestamp_millis() as f64 / 1000.0
+}
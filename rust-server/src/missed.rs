@@ -0,0 +1,159 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use cron::Schedule;
+use sqlx::{PgPool, Row};
+use std::str::FromStr;
+
+use crate::{
+    fail, warn,
+    alerts::{claim_alert_once, render_alert},
+    config::{Cadence, Config, ExpectedSchedule},
+    models::OverdueSchedule,
+    notify::{notify_all, NotifyLevel},
+    utils::format_local_datetime,
+};
+
+/// Returns every expected plan or repo that has missed a run as of `at`
+/// (or now, if `at` is in the future).
+///
+/// * Cron cadences are overdue once the first scheduled run after the latest
+///   event is more than `MISSED_GRACE_MINUTES` in the past.
+/// * Max-interval cadences are overdue once the latest event is older than
+///   the interval.
+///
+/// A plan or repo with no events at all is always overdue.
+pub async fn find_overdue(
+    cfg: &Config,
+    pool: &PgPool,
+    at: DateTime<Utc>,
+) -> Result<Vec<OverdueSchedule>, (StatusCode, &'static str)> {
+    let at = at.min(Utc::now());
+    let grace = Duration::minutes(cfg.missed.grace_minutes);
+    let mut overdue = Vec::new();
+
+    for expected in &cfg.missed.expected {
+        // 1) Latest event for this plan or repo, up to `at`
+        let last_seen = last_event_time(pool, expected, at).await.map_err(|e| {
+            fail!(cfg, "DB error", "missed backup lookup for {} '{}' failed: {}", expected.scope, expected.name, e)
+        })?;
+
+        // 2) When the next event was due
+        let (cadence, due_by) = match &expected.cadence {
+            Cadence::Cron(expr) => {
+                // Validated when the config was loaded
+                let schedule = Schedule::from_str(expr)
+                    .map_err(|e| fail!(cfg, "Config error", "invalid cron '{}': {}", expr, e))?;
+                let due_by = last_seen
+                    .and_then(|last| schedule.after(&last).next())
+                    .map(|fire| fire + grace);
+                (format!("cron {}", expr), due_by)
+            }
+            Cadence::MaxHours(hours) => (
+                format!("every {}h", hours),
+                last_seen.map(|last| last + Duration::hours(*hours)),
+            ),
+        };
+
+        // 3) Overdue if never seen, or the deadline has passed
+        let is_overdue = match (last_seen, due_by) {
+            (None, _)            => true,
+            (Some(_), Some(due)) => due < at,
+            (Some(_), None)      => false, // cron with no future runs
+        };
+        if is_overdue {
+            overdue.push(OverdueSchedule {
+                scope: expected.scope.to_string(),
+                name: expected.name.clone(),
+                cadence,
+                last_seen,
+                due_by,
+            });
+        }
+    }
+
+    Ok(overdue)
+}
+
+/// Runs the missed-backup check, alerting once per missed run and failing the
+/// healthcheck while anything is overdue.
+pub async fn check_missed_backups(cfg: &Config, pool: &PgPool) {
+    // 1) Find what is overdue right now
+    let overdue = match find_overdue(cfg, pool, Utc::now()).await {
+        Ok(o) => o,
+        Err(_) => return, // already logged and reported
+    };
+    if overdue.is_empty() {
+        tracing::info!("All {} expected schedule(s) are on time", cfg.missed.expected.len());
+        return;
+    }
+
+    // 2) Keep the healthcheck failing until everything catches up
+    let names: Vec<String> = overdue.iter().map(|o| format!("{} '{}'", o.scope, o.name)).collect();
+    warn!(
+        cfg.healthcheck_url,
+        "Missed backups",
+        "{} overdue: {}",
+        overdue.len(),
+        names.join(", ")
+    );
+
+    // 3) Alert once per missed run
+    for o in &overdue {
+        let key = format!(
+            "missed:{}:{}:{}",
+            o.scope,
+            o.name,
+            o.due_by.map(|d| d.timestamp()).unwrap_or(0)
+        );
+        match claim_alert_once(pool, &key).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                tracing::error!("Alert lookup for missed {} '{}' failed: {}", o.scope, o.name, e);
+                continue;
+            }
+        }
+
+        let notification = render_alert(
+            cfg,
+            format!("⏰ Missed backup: {} {}", o.scope, o.name),
+            NotifyLevel::Error,
+            &[
+                (if o.scope == "repo" { "Repo" } else { "Plan" }, o.name.clone()),
+                ("Expected", o.cadence.clone()),
+                ("Last event", o.last_seen.map(format_local_datetime).unwrap_or_else(|| "Never".into())),
+                ("Due by", o.due_by.map(format_local_datetime).unwrap_or_else(|| "–".into())),
+            ],
+            None,
+            "No event has been received since the expected run. Check that Backrest is running and the webhook is configured.",
+        );
+        match notify_all(cfg, &notification).await {
+            Ok(delivered) => tracing::info!(
+                "Alert '{}' sent to {} channel(s)",
+                notification.title,
+                delivered
+            ),
+            Err((_, msg)) => tracing::error!("Alert '{}' failed: {}", notification.title, msg),
+        }
+    }
+}
+
+/// Time of the latest event for the plan or repo, up to `at`.
+async fn last_event_time(
+    pool: &PgPool,
+    expected: &ExpectedSchedule,
+    at: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let sql = if expected.scope == "repo" {
+        "SELECT MAX(time) AS last_seen FROM summaries WHERE repo = $1 AND time <= $2"
+    } else {
+        "SELECT MAX(time) AS last_seen FROM summaries WHERE plan = $1 AND time <= $2"
+    };
+    let row = sqlx::query(sql)
+        .bind(&expected.name)
+        .bind(at)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(row.get("last_seen"))
+}
@@ -1,7 +1,9 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::config::ComparePeriods;
+
 /// Represents the nested snapshot_stats in the incoming JSON payload.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SnapshotStats {
@@ -43,6 +45,34 @@ pub struct SummaryPayload {
     pub snapshot_stats:  Option<SnapshotStats>,
 }
 
+/// Response type for `/add-event`
+#[derive(Debug, Serialize)]
+pub struct AddEventResponse {
+    pub summary_id: i32,
+    pub created_at: DateTime<Utc>,
+    /// True when the event was a replay of one already stored
+    pub duplicate:  bool,
+}
+
+/// Per-item outcome of a `/add-events` batch
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    /// Position of the item in the submitted batch (0-based)
+    pub index:      usize,
+    pub summary_id: Option<i32>,
+    pub duplicate:  bool,
+    pub error:      Option<String>,
+}
+
+/// Response type for `/add-events`
+#[derive(Debug, Serialize)]
+pub struct AddEventsResponse {
+    pub inserted:   usize,
+    pub duplicates: usize,
+    pub failed:     usize,
+    pub results:    Vec<BatchItemResult>,
+}
+
 /// Request type for snapshot event stats
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StatsRequest {
@@ -50,6 +80,60 @@ pub struct StatsRequest {
     pub end_date:   DateTime<Utc>,
 }
 
+/// One event with the events related to it, oldest first
+#[derive(Serialize)]
+pub struct EventDetail {
+    pub event:   CombinedStats,
+    pub related: Vec<CombinedStats>,
+}
+
+/// Query parameters of `GET /events`; every filter is optional
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Earliest event time, inclusive
+    pub start:  Option<DateTime<Utc>>,
+    /// Latest event time, inclusive
+    pub end:    Option<DateTime<Utc>>,
+    pub repo:   Option<String>,
+    pub plan:   Option<String>,
+    /// Event type such as `snapshot`, or a full event such as `snapshot error`
+    pub event:  Option<String>,
+    /// `success`, `warning` or `error`
+    pub status: Option<String>,
+    /// Case-insensitive text to find in the event or snapshot error
+    pub q:      Option<String>,
+    /// `desc` (newest first, default) or `asc`, by event time
+    pub sort:   Option<String>,
+    pub limit:  Option<i64>,
+    pub offset: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+/// Query parameters of `/export/events` and `/export/storage`
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    /// Start of the range, inclusive
+    pub start:  DateTime<Utc>,
+    /// End of the range, inclusive
+    pub end:    DateTime<Utc>,
+    /// `csv` or `ndjson`; overrides the `Accept` header
+    pub format: Option<String>,
+}
+
+/// One page of `GET /events`
+#[derive(Serialize)]
+pub struct EventsPage {
+    /// Events matching the filters, across all pages
+    pub total:       i64,
+    pub limit:       i64,
+    /// Rows skipped, unless paging by cursor
+    pub offset:      Option<i64>,
+    /// Pass as `cursor` for the next page; `null` on the last page
+    pub next_cursor: Option<String>,
+    pub events:      Vec<CombinedStats>,
+}
+
 /// Request type for storage stats
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StorageStatsRequest {
@@ -140,6 +224,58 @@ pub struct EventTotalsReport {
     pub previous_month: Option<EventTotals>,
 }
 
+/// Column to group event totals by
+#[derive(Debug, Clone, Copy)]
+pub enum TotalsGroup {
+    Repo,
+    Plan,
+}
+
+/// Which timestamp places an event in a report window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTime {
+    /// When Backrest says the event happened (`summaries.time`)
+    Occurred,
+    /// When the event was stored (`summaries.created_at`)
+    Ingested,
+}
+
+impl EventTime {
+    /// Column of `summaries s` holding this timestamp
+    pub fn column(self) -> &'static str {
+        match self {
+            EventTime::Occurred => "s.time",
+            EventTime::Ingested => "s.created_at",
+        }
+    }
+}
+
+/// Aggregated event totals for a single repo or plan
+#[derive(Serialize, FromRow)]
+pub struct NamedEventTotals {
+    pub name:   String,
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub totals: EventTotals,
+}
+
+/// Event totals for one repo or plan, with the same comparisons as `EventTotalsReport`
+#[derive(Serialize)]
+pub struct GroupTotalsReport {
+    pub name:           String,
+    pub current:        EventTotals,
+    pub previous_day:   Option<EventTotals>,
+    pub previous_week:  Option<EventTotals>,
+    pub previous_month: Option<EventTotals>,
+}
+
+/// Event totals broken down per repo and per plan
+#[derive(Serialize)]
+pub struct TotalsBreakdown {
+    pub by_repo: Vec<GroupTotalsReport>,
+    pub by_plan: Vec<GroupTotalsReport>,
+}
+
 /// Storing the stats for a storage
 #[derive(Serialize)]
 pub struct StorageReport {
@@ -169,18 +305,142 @@ pub struct CurrentStorageStats {
     pub previous_day:   Option<PeriodStats>,
     pub previous_week:  Option<PeriodStats>,
     pub previous_month: Option<PeriodStats>,
+    /// `ok`, `warning` or `critical`, against the mount's thresholds
+    pub usage_level:    &'static str,
+    /// Projected fill dates; `None` until there are enough samples
+    pub forecast:       Option<StorageForecast>,
+}
+
+/// When a mount is projected to reach the warning threshold and fill up, from
+/// its growth over the forecast window
+#[derive(Serialize)]
+pub struct StorageForecast {
+    /// Fitted growth in bytes per day; negative when shrinking
+    pub growth_bytes_per_day: f64,
+    /// Samples the growth was fitted to, over `window_days`
+    pub samples:              i64,
+    pub window_days:          i64,
+    pub warn_percent:         f64,
+    /// Days until `warn_percent` is reached; 0 once past it, `None` if not growing
+    pub days_until_warn:      Option<f64>,
+    pub warn_at:              Option<DateTime<Utc>>,
+    /// Days until the mount is full; `None` if not growing
+    pub days_until_full:      Option<f64>,
+    pub full_at:              Option<DateTime<Utc>>,
+}
+
+/// A plan or repo whose expected run has not produced an event
+#[derive(Debug, Serialize)]
+pub struct OverdueSchedule {
+    /// `"plan"` or `"repo"`
+    pub scope:     String,
+    pub name:      String,
+    /// Human-readable expected cadence, e.g. `cron 0 0 2 * * *` or `every 26h`
+    pub cadence:   String,
+    /// Time of the latest event, if one was ever recorded
+    pub last_seen: Option<DateTime<Utc>>,
+    /// When the missed run was due (including the grace period)
+    pub due_by:    Option<DateTime<Utc>>,
 }
 
 /// Combined report of event totals, summary statistics, and current storage stats
 #[derive(Serialize)]
 pub struct GenerateReport {
+    /// Name of the scheduled report, if it is a named one
+    pub name:               Option<String>,
+    /// Comparison periods shown; the others are left empty
+    pub compare:            ComparePeriods,
     pub event_totals:       EventTotalsReport,
     pub snapshot_summaries: Vec<CombinedStats>,
     pub storage_statistics: Vec<CurrentStorageStats>,
+    pub overdue_schedules:  Vec<OverdueSchedule>,
+    pub totals_breakdown:   TotalsBreakdown,
+    pub trends:             ReportTrends,
+}
+
+/// Last storage sample of one (local) day for a mount
+#[derive(Serialize, FromRow)]
+pub struct StorageDay {
+    pub day:         NaiveDate,
+    pub used_bytes:  i64,
+    pub total_bytes: i64,
+}
+
+/// Daily storage samples for one mount, oldest first
+#[derive(Serialize)]
+pub struct StorageTrend {
+    pub location: String,
+    pub days:     Vec<StorageDay>,
+}
+
+/// Snapshot activity of one (local) day
+#[derive(Serialize, FromRow)]
+pub struct DailyActivity {
+    pub day:            NaiveDate,
+    pub snapshots:      i64,
+    pub data_added:     i64,
+    pub total_duration: i64,
+}
+
+/// Per-day history charted in the report
+#[derive(Serialize)]
+pub struct ReportTrends {
+    pub storage: Vec<StorageTrend>,
+    pub daily:   Vec<DailyActivity>,
+}
+
+/// One recorded run of a scheduled job
+#[derive(Serialize, FromRow)]
+pub struct JobRun {
+    pub id:          i64,
+    pub started_at:  DateTime<Utc>,
+    /// `None` while still running
+    pub finished_at: Option<DateTime<Utc>>,
+    /// `running`, `success`, `failure`, or `interrupted`
+    pub outcome:     String,
+    pub error:       Option<String>,
+}
+
+/// Window covered by a named report sent on request
+#[derive(Serialize)]
+pub struct SentReport {
+    pub report:     String,
+    pub start_date: DateTime<Utc>,
+    pub end_date:   DateTime<Utc>,
+    /// Number of notification channels that delivered it
+    pub delivered:  usize,
+}
+
+/// A registered scheduled job, its next fire time and its latest runs
+#[derive(Serialize)]
+pub struct JobStatus {
+    pub name:        String,
+    /// Cron expression, in UTC
+    pub schedule:    String,
+    pub next_run:    Option<DateTime<Utc>>,
+    /// Newest first
+    pub recent_runs: Vec<JobRun>,
+}
+
+/// Outcome of one `/readyz` check
+#[derive(Serialize)]
+pub struct ReadinessCheck {
+    /// `database`, `smtp`, or `storage:<path>`
+    pub name:        String,
+    pub ok:          bool,
+    pub error:       Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Every `/readyz` check; `ready` only if all of them passed
+#[derive(Serialize)]
+pub struct Readiness {
+    pub ready:  bool,
+    pub checks: Vec<ReadinessCheck>,
 }
 
 /// Structure matching exactly the columns pulled from the DB
-#[derive(Clone, FromRow)]
+#[derive(Clone, Serialize, FromRow)]
 pub struct DbStorageRow {
     pub storage_location:    String,
     pub storage_nickname:    Option<String>,
@@ -0,0 +1,459 @@
+use axum::http::StatusCode;
+use futures_util::future::join_all;
+use reqwest::Client;
+use serde_json::json;
+use std::time::Duration;
+
+use crate::{
+    fail, warn,
+    config::Config,
+    email::EmailClient,
+    forecast::format_projection,
+    html_report::{format_bytes, format_duration_secs},
+    models::{EventTotals, GenerateReport},
+    utils::format_local_datetime,
+};
+
+/// Severity of a notification, used for channel priorities and tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotifyLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl NotifyLevel {
+    /// Classifies a set of event totals by its worst outcome.
+    pub fn for_event_totals(et: &EventTotals) -> Self {
+        let errors = et.total_snapshot_error
+            + et.total_forget_error
+            + et.total_prune_error
+            + et.total_check_error;
+        let warnings = et.total_snapshot_warning
+            + et.total_forget_warning
+            + et.total_prune_warning
+            + et.total_check_warning;
+        if errors > 0 {
+            NotifyLevel::Error
+        } else if warnings > 0 {
+            NotifyLevel::Warning
+        } else {
+            NotifyLevel::Success
+        }
+    }
+
+    /// Stable lowercase name, e.g. for payloads and keys
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotifyLevel::Info    => "info",
+            NotifyLevel::Success => "success",
+            NotifyLevel::Warning => "warning",
+            NotifyLevel::Error   => "error",
+        }
+    }
+}
+
+/// One message, pre-rendered for every kind of channel.
+pub struct Notification {
+    /// Subject line / title
+    pub title:    String,
+    /// Full HTML body, used for email
+    pub html:     String,
+    /// Plain-text body, sent with the HTML as the email's alternative part
+    pub text:     String,
+    /// Condensed markdown body, used for chat and push channels
+    pub markdown: String,
+    pub level:    NotifyLevel,
+}
+
+/// A configured destination for notifications
+#[derive(Debug, Clone)]
+pub enum Channel {
+    Email,
+    Webhook { url: String },
+    Ntfy    { url: String, token: Option<String> },
+    Gotify  { url: String, token: String },
+    Slack   { url: String },
+    Discord { url: String },
+}
+
+impl Channel {
+    /// Every channel enabled in the configuration. Email is enabled by `SMTP_HOST`.
+    pub fn all_from_config(cfg: &Config) -> Vec<Channel> {
+        let n = &cfg.notify;
+        let mut channels = Vec::new();
+
+        if cfg.smtp_host.as_deref().is_some_and(|h| !h.trim().is_empty()) {
+            channels.push(Channel::Email);
+        }
+        if let Some(url) = &n.webhook_url {
+            channels.push(Channel::Webhook { url: url.clone() });
+        }
+        if let Some(url) = &n.ntfy_url {
+            channels.push(Channel::Ntfy { url: url.clone(), token: n.ntfy_token.clone() });
+        }
+        if let (Some(url), Some(token)) = (&n.gotify_url, &n.gotify_token) {
+            channels.push(Channel::Gotify { url: url.clone(), token: token.clone() });
+        }
+        if let Some(url) = &n.slack_webhook_url {
+            channels.push(Channel::Slack { url: url.clone() });
+        }
+        if let Some(url) = &n.discord_webhook_url {
+            channels.push(Channel::Discord { url: url.clone() });
+        }
+
+        channels
+    }
+
+    /// Short name for logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Email          => "email",
+            Channel::Webhook { .. } => "webhook",
+            Channel::Ntfy { .. }    => "ntfy",
+            Channel::Gotify { .. }  => "gotify",
+            Channel::Slack { .. }   => "slack",
+            Channel::Discord { .. } => "discord",
+        }
+    }
+
+    /// Delivers the notification, rendered appropriately for this channel.
+    pub async fn send(
+        &self,
+        cfg: &Config,
+        http: &Client,
+        n: &Notification,
+    ) -> Result<(), String> {
+        let request = match self {
+            Channel::Email => {
+                let client = EmailClient::from_config(cfg).map_err(|(_, e)| e.to_string())?;
+                return client
+                    .send(&n.title, n.html.clone(), n.text.clone(), cfg)
+                    .await
+                    .map_err(|(_, e)| e.to_string());
+            }
+            Channel::Webhook { url } => http.post(url).json(&json!({
+                "title":       n.title,
+                "message":     n.markdown,
+                "level":       n.level.as_str(),
+                "server_name": cfg.server_name,
+                "version":     cfg.version,
+            })),
+            Channel::Ntfy { url, token } => {
+                // JSON publishing keeps emoji in the title intact
+                let (server, topic) = url
+                    .trim_end_matches('/')
+                    .rsplit_once('/')
+                    .ok_or_else(|| format!("NTFY_URL '{}' has no topic", url))?;
+                let (priority, tag) = match n.level {
+                    NotifyLevel::Error   => (5, "x"),
+                    NotifyLevel::Warning => (4, "warning"),
+                    NotifyLevel::Success => (3, "white_check_mark"),
+                    NotifyLevel::Info    => (3, "information_source"),
+                };
+                let req = http.post(server).json(&json!({
+                    "topic":    topic,
+                    "title":    n.title,
+                    "message":  n.markdown,
+                    "markdown": true,
+                    "priority": priority,
+                    "tags":     [tag],
+                }));
+                match token {
+                    Some(t) => req.bearer_auth(t),
+                    None    => req,
+                }
+            }
+            Channel::Gotify { url, token } => {
+                let priority = match n.level {
+                    NotifyLevel::Error   => 8,
+                    NotifyLevel::Warning => 5,
+                    _                    => 2,
+                };
+                http.post(format!("{}/message", url.trim_end_matches('/')))
+                    .header("X-Gotify-Key", token)
+                    .json(&json!({
+                        "title":    n.title,
+                        "message":  n.markdown,
+                        "priority": priority,
+                        "extras": {
+                            "client::display": { "contentType": "text/markdown" }
+                        },
+                    }))
+            }
+            Channel::Slack { url } => {
+                let text = format!("*{}*\n{}", n.title, slack_mrkdwn(&n.markdown));
+                http.post(url).json(&json!({ "text": text }))
+            }
+            Channel::Discord { url } => {
+                let content = truncate_chars(&format!("**{}**\n{}", n.title, n.markdown), DISCORD_MAX_CHARS);
+                http.post(url).json(&json!({ "content": content }))
+            }
+        };
+
+        let resp = request.send().await.map_err(|e| e.to_string())?;
+        if resp.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("returned {}", resp.status()))
+        }
+    }
+}
+
+/// Discord rejects messages longer than this
+const DISCORD_MAX_CHARS: usize = 2000;
+
+/// Sends the notification to every configured channel at once.
+///
+/// Failures on individual channels are logged and reported to the healthcheck;
+/// the call only fails if no channel is configured or none delivered.
+/// Returns the number of channels that delivered.
+pub async fn notify_all(
+    cfg: &Config,
+    notification: &Notification,
+) -> Result<usize, (StatusCode, &'static str)> {
+    let channels = Channel::all_from_config(cfg);
+    if channels.is_empty() {
+        return Err(fail!(cfg, "Notification config error", "no notification channels configured{}", ""));
+    }
+
+    let http = Client::builder()
+        .timeout(Duration::from_secs(cfg.notify.timeout))
+        .build()
+        .map_err(|e| fail!(cfg, "Notification config error", "HTTP client setup failed: {}", e))?;
+    let results = join_all(channels.iter().map(|channel| channel.send(cfg, &http, notification))).await;
+
+    let mut delivered = 0;
+    for (channel, result) in channels.iter().zip(results) {
+        match result {
+            Ok(()) => {
+                tracing::info!("Notification '{}' sent via {}", notification.title, channel.name());
+                delivered += 1;
+            }
+            Err(e) => {
+                warn!(
+                    cfg.healthcheck_url,
+                    "Notification error",
+                    "'{}' via {} failed: {}",
+                    notification.title,
+                    channel.name(),
+                    e
+                );
+            }
+        }
+    }
+
+    if delivered == 0 {
+        return Err(fail!(
+            cfg,
+            "Notification send error",
+            "'{}' could not be delivered to any of {} channel(s)",
+            notification.title,
+            channels.len()
+        ));
+    }
+
+    Ok(delivered)
+}
+
+/// Condensed markdown version of the report for chat and push channels.
+pub fn render_report_markdown(cfg: &Config, report: &GenerateReport) -> String {
+    let et = &report.event_totals.current;
+    let mut out = String::new();
+
+    if let Some(name) = cfg.server_name.as_deref().filter(|s| !s.is_empty()) {
+        out.push_str(&format!("**Server:** {}\n", name));
+    }
+
+    // Event counts as success / warning / error per type
+    out.push_str(&format!(
+        "**Snapshots:** ✅ {} · ⚠️ {} · ❌ {}\n",
+        et.total_snapshot_success, et.total_snapshot_warning, et.total_snapshot_error
+    ));
+    out.push_str(&format!(
+        "**Forget:** ✅ {} · ⚠️ {} · ❌ {}\n",
+        et.total_forget_success, et.total_forget_warning, et.total_forget_error
+    ));
+    out.push_str(&format!(
+        "**Prune:** ✅ {} · ⚠️ {} · ❌ {}\n",
+        et.total_prune_success, et.total_prune_warning, et.total_prune_error
+    ));
+    out.push_str(&format!(
+        "**Check:** ✅ {} · ⚠️ {} · ❌ {}\n",
+        et.total_check_success, et.total_check_warning, et.total_check_error
+    ));
+    out.push_str(&format!(
+        "**Data added:** {} · **Processed:** {} · **Duration:** {}\n",
+        format_bytes(et.total_data_added as u64),
+        format_bytes(et.total_bytes_processed as u64),
+        format_duration_secs(et.total_duration)
+    ));
+
+    // One line per repo
+    if report.totals_breakdown.by_repo.len() > 1 {
+        out.push_str("\n**Repositories**\n");
+        for group in &report.totals_breakdown.by_repo {
+            let g = &group.current;
+            out.push_str(&format!(
+                "- {}: ✅ {} · ⚠️ {} · ❌ {} · {} added\n",
+                group.name,
+                g.total_snapshot_success + g.total_forget_success + g.total_prune_success + g.total_check_success,
+                g.total_snapshot_warning + g.total_forget_warning + g.total_prune_warning + g.total_check_warning,
+                g.total_snapshot_error + g.total_forget_error + g.total_prune_error + g.total_check_error,
+                format_bytes(g.total_data_added.max(0) as u64)
+            ));
+        }
+    }
+
+    // Plans or repos that missed an expected run
+    if !report.overdue_schedules.is_empty() {
+        out.push_str("\n**Overdue**\n");
+        for o in &report.overdue_schedules {
+            out.push_str(&format!(
+                "- ⏰ {} {} ({}), last event {}\n",
+                o.scope,
+                o.name,
+                o.cadence,
+                o.last_seen.map(format_local_datetime).unwrap_or_else(|| "never".into())
+            ));
+        }
+    }
+
+    // Storage usage, one line per mount
+    if !report.storage_statistics.is_empty() {
+        out.push_str("\n**Storage**\n");
+        for stat in &report.storage_statistics {
+            let name = stat.nickname.as_deref().filter(|s| !s.is_empty()).unwrap_or(&stat.location);
+            let full = stat
+                .forecast
+                .as_ref()
+                .filter(|f| f.growth_bytes_per_day > 0.0)
+                .map(|f| format!(", full {}", format_projection(f.days_until_full, f.full_at)))
+                .unwrap_or_default();
+            let marker = match stat.usage_level {
+                "critical" => "🔴 ",
+                "warning"  => "🟡 ",
+                _          => "",
+            };
+            out.push_str(&format!(
+                "- {}{}: {:.1}% used ({} of {}){}\n",
+                marker,
+                name,
+                stat.current.percent_used,
+                format_bytes(stat.current.used_bytes as u64),
+                format_bytes(stat.current.total_bytes as u64),
+                full
+            ));
+        }
+    }
+
+    // Every failed or warned event, with its message
+    let problems: Vec<_> = report
+        .snapshot_summaries
+        .iter()
+        .filter(|s| s.event.contains("error") || s.event.contains("warning"))
+        .collect();
+    if !problems.is_empty() {
+        out.push_str("\n**Problems**\n");
+        for s in problems {
+            out.push_str(&format!(
+                "- {} / {}: {}{}\n",
+                s.repo,
+                s.plan,
+                s.event,
+                s.error.as_deref().map(|e| format!(" – {}", e)).unwrap_or_default()
+            ));
+        }
+    }
+
+    if let Some(url) = cfg.backrest_url.as_deref().filter(|s| !s.is_empty()) {
+        out.push_str(&format!("\n[Open Backrest]({})\n", url));
+    }
+
+    out
+}
+
+/// Converts the condensed markdown to Slack's mrkdwn: `**bold**` becomes
+/// `*bold*` and `[text](url)` becomes `<url|text>`. Link URLs and code spans
+/// are kept as written; `&`, `<` and `>` are escaped everywhere else.
+pub fn slack_mrkdwn(markdown: &str) -> String {
+    let mut out = String::new();
+    let mut rest = markdown;
+
+    while let Some(pos) = rest.find(['*', '`', '[']) {
+        out.push_str(&slack_escape(&rest[..pos]));
+        rest = &rest[pos..];
+
+        // Bold markers
+        if let Some(after) = rest.strip_prefix("**") {
+            out.push('*');
+            rest = after;
+            continue;
+        }
+        // Code spans, up to the closing backtick
+        if rest.starts_with('`') {
+            let end = rest[1..].find('`').map_or(rest.len(), |i| i + 2);
+            out.push_str(&slack_escape(&rest[..end]));
+            rest = &rest[end..];
+            continue;
+        }
+        // Links, with bold still converted in their text
+        if let Some((text, url, after)) = markdown_link(rest) {
+            out.push_str(&format!("<{}|{}>", url, slack_mrkdwn(text)));
+            rest = after;
+            continue;
+        }
+        // A lone `*` or `[`
+        out.push_str(&slack_escape(&rest[..1]));
+        rest = &rest[1..];
+    }
+    out.push_str(&slack_escape(rest));
+
+    out
+}
+
+/// Splits `[text](url)` at the start of `s` into the text, the URL and what follows.
+fn markdown_link(s: &str) -> Option<(&str, &str, &str)> {
+    let (text, tail) = s.strip_prefix('[')?.split_once("](")?;
+    let (url, after) = tail.split_once(')')?;
+    if text.contains(['[', '\n']) || url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((text, url, after))
+}
+
+/// Escapes the characters Slack treats as control sequences.
+fn slack_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Cuts a string to at most `max` characters, marking the cut with an ellipsis.
+pub fn truncate_chars(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slack_bold_and_links() {
+        assert_eq!(
+            slack_mrkdwn("**Server:** nas\n[Open Backrest](https://br.local/?a=1&b=2)"),
+            "*Server:* nas\n<https://br.local/?a=1&b=2|Open Backrest>"
+        );
+        assert_eq!(slack_mrkdwn("[**Docs**](https://x.y/a**b)"), "<https://x.y/a**b|*Docs*>");
+    }
+
+    #[test]
+    fn slack_keeps_code_and_escapes_text() {
+        assert_eq!(slack_mrkdwn("`a**b` <x> & [y]"), "`a**b` &lt;x&gt; &amp; [y]");
+        assert_eq!(slack_mrkdwn("5 * 3"), "5 * 3");
+    }
+}
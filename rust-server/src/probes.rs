@@ -0,0 +1,83 @@
+use sqlx::PgPool;
+use std::{fs, future::Future, time::{Duration, Instant}};
+
+use crate::{
+    config::Config,
+    email::EmailClient,
+    models::{Readiness, ReadinessCheck},
+};
+
+/// Longest a single readiness check may take before it counts as failed
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs every readiness check: the database, each configured storage mount,
+/// and the SMTP server if `READY_CHECK_SMTP` is set.
+pub async fn check_readiness(pool: &PgPool, cfg: &Config) -> Readiness {
+    let mut checks = Vec::new();
+
+    // 1) A cheap query proves the pool can hand out a working connection
+    checks.push(
+        run_check("database".into(), async {
+            sqlx::query("SELECT 1")
+                .execute(pool)
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .await,
+    );
+
+    // 2) Mounts are listed on a blocking thread, as a hung network mount
+    //    would otherwise stall the runtime
+    for mount in &cfg.storage_mounts {
+        let path = mount.path.clone();
+        checks.push(
+            run_check(format!("storage:{}", mount.path), async move {
+                tokio::task::spawn_blocking(move || fs::read_dir(&path).map(|_| ()))
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .map_err(|e| e.to_string())
+            })
+            .await,
+        );
+    }
+
+    // 3) SMTP, only when asked for, as it opens a connection every probe
+    if cfg.ready_check_smtp {
+        checks.push(
+            run_check("smtp".into(), async {
+                // Not from_config: a misconfiguration must not ping the healthcheck every probe
+                let client = EmailClient::build(cfg)?;
+                client.test_connection().await
+            })
+            .await,
+        );
+    }
+
+    Readiness {
+        ready: checks.iter().all(|c| c.ok),
+        checks,
+    }
+}
+
+/// Times `check`, failing it if it takes longer than `CHECK_TIMEOUT`.
+async fn run_check<Fut>(name: String, check: Fut) -> ReadinessCheck
+where
+    Fut: Future<Output = Result<(), String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    if let Err(e) = &result {
+        tracing::warn!("Readiness check {} failed: {}", name, e);
+    }
+
+    ReadinessCheck {
+        name,
+        ok:          result.is_ok(),
+        error:       result.err(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
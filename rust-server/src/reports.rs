@@ -0,0 +1,131 @@
+use axum::http::StatusCode;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    fail,
+    config::{Config, ReportDefinition, ReportWindow},
+    db::wait_for_ingests,
+    handlers::generate_and_send_report,
+    models::{EventTime, SentReport},
+};
+
+/// The scheduled report called `name`, if one is configured.
+pub fn find_report<'a>(cfg: &'a Config, name: &str) -> Option<&'a ReportDefinition> {
+    cfg.reports.iter().find(|r| r.name == name)
+}
+
+/// Label shown in the report title; the `default` report has none.
+pub fn report_label(report: &ReportDefinition) -> Option<&str> {
+    Some(report.name.as_str()).filter(|name| *name != "default")
+}
+
+/// Start of `report`'s window ending at `end`: right after the previous one
+/// sent in aligned mode, otherwise (or before the first) its interval earlier.
+pub async fn report_start(pool: &PgPool, report: &ReportDefinition, end: DateTime<Utc>) -> DateTime<Utc> {
+    let interval_start = end - Duration::hours(report.interval_hours);
+    if report.window != ReportWindow::Aligned {
+        return interval_start;
+    }
+
+    match last_report_end(pool, &report.name).await {
+        Ok(Some(last_end)) if last_end < end => just_after(last_end),
+        Ok(_) => interval_start,
+        Err(e) => {
+            tracing::error!(
+                "Looking up the last '{}' report failed, using its interval: {}",
+                report.name,
+                e
+            );
+            interval_start
+        }
+    }
+}
+
+/// Earliest time after `t` that Postgres can store. Report queries include
+/// both ends of their window, so a window starting here shares no event with
+/// one ending at `t`.
+pub fn just_after(t: DateTime<Utc>) -> DateTime<Utc> {
+    t + Duration::microseconds(1)
+}
+
+/// Sends `report` for the window ending at `end` and records the window as
+/// covered, so aligned windows and catch-up continue from it.
+pub async fn send_scheduled_report(
+    cfg: &Config,
+    pool: &PgPool,
+    report: &ReportDefinition,
+    end: DateTime<Utc>,
+    catch_up: bool,
+) -> Result<SentReport, (StatusCode, &'static str)> {
+    let start = report_start(pool, report, end).await;
+    send_report_window(cfg, pool, report, start, end, catch_up).await
+}
+
+/// Sends `report` for `start` to `end` and records the window as covered.
+///
+/// Aligned windows select events by when they were stored rather than when
+/// they happened, so an event that arrives late (a Backrest retry or an
+/// `/add-events` backfill) still lands in the next report instead of a window
+/// that was already sent. They first wait for ingest transactions in flight,
+/// so an event stamped inside the window but not yet committed isn't skipped.
+pub async fn send_report_window(
+    cfg: &Config,
+    pool: &PgPool,
+    report: &ReportDefinition,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    catch_up: bool,
+) -> Result<SentReport, (StatusCode, &'static str)> {
+    let by = match report.window {
+        ReportWindow::Aligned  => EventTime::Ingested,
+        ReportWindow::Interval => EventTime::Occurred,
+    };
+    if by == EventTime::Ingested {
+        wait_for_ingests(pool)
+            .await
+            .map_err(|e| fail!(cfg, "DB error", "waiting for event ingestion failed: {}", e))?;
+    }
+    let delivered =
+        generate_and_send_report(cfg, pool, report_label(report), &report.content, start, end, by).await?;
+
+    if let Err(e) = record_report_window(pool, &report.name, start, end, catch_up).await {
+        tracing::error!("Recording '{}' report window failed: {}", report.name, e);
+    }
+
+    Ok(SentReport {
+        report:     report.name.clone(),
+        start_date: start,
+        end_date:   end,
+        delivered,
+    })
+}
+
+/// Records the window covered by a scheduled report that was sent.
+async fn record_report_window(
+    pool: &PgPool,
+    report: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    catch_up: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO report_windows (report, window_start, window_end, catch_up) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(report)
+    .bind(start)
+    .bind(end)
+    .bind(catch_up)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// End of the latest window covered by the named report, if it was ever sent.
+pub async fn last_report_end(pool: &PgPool, report: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT MAX(window_end) FROM report_windows WHERE report = $1")
+        .bind(report)
+        .fetch_one(pool)
+        .await
+}
@@ -0,0 +1,139 @@
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    fail,
+    config::Config,
+};
+
+/// Number of rows removed from each table by one pruning pass
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub summaries:           u64,
+    pub snapshot_stats:      u64,
+    pub storage_downsampled: u64,
+    pub storage_expired:     u64,
+    pub job_runs:            u64,
+    pub alert_state:         u64,
+}
+
+/// Alert rate-limit entries idle for this long are forgotten
+const ALERT_STATE_DAYS: i64 = 30;
+
+/// Deletes rows older than the configured retention windows.
+///
+/// * `summaries` older than `RETENTION_EVENTS_DAYS` are removed, cascading to
+///   their `snapshot_stats`.
+/// * `snapshot_stats` older than `RETENTION_SNAPSHOT_STATS_DAYS` are removed,
+///   keeping the event row itself.
+/// * `storage` samples older than `RETENTION_STORAGE_RAW_DAYS` are reduced to
+///   the last sample of each UTC day per mount, so day/week/month comparisons
+///   still find a prior row.
+/// * Daily `storage` samples older than `RETENTION_STORAGE_DAILY_DAYS` are removed.
+/// * Finished `job_runs` older than `RETENTION_JOB_RUNS_DAYS` are removed.
+/// * `alert_state` entries not sent for `ALERT_STATE_DAYS`, or the alert
+///   cooldown if longer, are removed. A missed run still overdue by then
+///   alerts again as a reminder.
+pub async fn prune_old_data(
+    pool: &PgPool,
+    cfg: &Config,
+) -> Result<PruneReport, (StatusCode, &'static str)> {
+    let retention = &cfg.retention;
+    let now = Utc::now();
+    let mut report = PruneReport::default();
+
+    // 1) Events, with their stats via ON DELETE CASCADE
+    if let Some(days) = retention.summaries_days {
+        report.summaries = sqlx::query("DELETE FROM summaries WHERE time < $1")
+            .bind(now - Duration::days(days))
+            .execute(pool)
+            .await
+            .map_err(|e| fail!(cfg, "DB error", "prune summaries failed: {}", e))?
+            .rows_affected();
+    }
+
+    // 2) Detailed snapshot stats only
+    if let Some(days) = retention.snapshot_stats_days {
+        report.snapshot_stats = sqlx::query(
+            r#"
+            DELETE FROM snapshot_stats ss
+            USING summaries s
+            WHERE ss.summary_id = s.id
+              AND s.time < $1
+            "#,
+        )
+        .bind(now - Duration::days(days))
+        .execute(pool)
+        .await
+        .map_err(|e| fail!(cfg, "DB error", "prune snapshot_stats failed: {}", e))?
+        .rows_affected();
+    }
+
+    // 3) Downsample old storage samples to the last one per day per mount
+    if let Some(days) = retention.storage_raw_days {
+        report.storage_downsampled = sqlx::query(
+            r#"
+            DELETE FROM storage s
+            USING (
+                SELECT
+                    id,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY storage_location, date_trunc('day', time_added AT TIME ZONE 'UTC')
+                        ORDER BY time_added DESC, id DESC
+                    ) AS rn
+                FROM storage
+                WHERE time_added < $1
+            ) ranked
+            WHERE s.id = ranked.id
+              AND ranked.rn > 1
+            "#,
+        )
+        .bind(now - Duration::days(days))
+        .execute(pool)
+        .await
+        .map_err(|e| fail!(cfg, "DB error", "downsample storage failed: {}", e))?
+        .rows_affected();
+    }
+
+    // 4) Expire the daily samples entirely
+    if let Some(days) = retention.storage_daily_days {
+        report.storage_expired = sqlx::query("DELETE FROM storage WHERE time_added < $1")
+            .bind(now - Duration::days(days))
+            .execute(pool)
+            .await
+            .map_err(|e| fail!(cfg, "DB error", "prune storage failed: {}", e))?
+            .rows_affected();
+    }
+
+    // 5) Finished job runs; a run still in progress is kept
+    if let Some(days) = retention.job_runs_days {
+        report.job_runs = sqlx::query("DELETE FROM job_runs WHERE started_at < $1 AND finished_at IS NOT NULL")
+            .bind(now - Duration::days(days))
+            .execute(pool)
+            .await
+            .map_err(|e| fail!(cfg, "DB error", "prune job_runs failed: {}", e))?
+            .rows_affected();
+    }
+
+    // 6) Idle alert rate-limit entries, never while their cooldown applies
+    let idle = Duration::days(ALERT_STATE_DAYS).max(Duration::minutes(cfg.alerts.cooldown_minutes));
+    report.alert_state = sqlx::query("DELETE FROM alert_state WHERE last_sent_at < $1")
+        .bind(now - idle)
+        .execute(pool)
+        .await
+        .map_err(|e| fail!(cfg, "DB error", "prune alert_state failed: {}", e))?
+        .rows_affected();
+
+    tracing::info!(
+        "Pruned {} events, {} snapshot stats, {} downsampled and {} expired storage samples, {} job runs and {} alert states",
+        report.summaries,
+        report.snapshot_stats,
+        report.storage_downsampled,
+        report.storage_expired,
+        report.job_runs,
+        report.alert_state
+    );
+
+    Ok(report)
+}
@@ -1,238 +1,374 @@
-use chrono::{DateTime, Local, Utc, Duration as ChronoDuration};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, Utc};
 use cron::Schedule;
-use reqwest::Client;
-use std::{fs, str::FromStr};
+use minijinja::context;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::str::FromStr;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use tracing::{info, error};
+use sqlx::PgPool;
 use crate::{
-    fail, ok,
-    config::Config,
-    email::{EmailClient},
-    utils::{container_id_from_hostname, format_local_datetime},
+    ok,
+    config::{Config, ReportCatchUp, ReportDefinition},
+    forecast::check_storage_forecasts,
+    handlers::load_and_insert_storage_stats,
+    jobs::{mark_interrupted_runs, run_recorded, JobKind},
+    missed::check_missed_backups,
+    notify::{notify_all, Notification, NotifyLevel},
+    reports::{just_after, last_report_end, send_report_window, send_scheduled_report},
+    retention::prune_old_data,
+    templates,
+    text_report::render_startup_text,
+    utils::container_id_from_hostname,
 };
 
+/// Format used when logging upcoming runs
+const TS_FMT: &str = "%a, %b %e %Y at %I:%M:%S %p %:z";
+/// Most missed reports sent one by one on startup; older ones are skipped
+const MAX_CATCH_UP_REPORTS: usize = 24;
 
-/// Spawns a cron job that calls the `/generate-and-send-email-report` endpoint.
-pub async fn spawn_email_report_cron(cfg: Config) {
-    // clone once for the async task
-    let cfg = cfg.clone();
-    let http = Client::new();
-    
+/// Registers every background job on one shared scheduler and starts it.
+///
+/// # Errors
+/// Fails if any configured cron expression or report template is invalid.
+pub fn start_scheduler(cfg: Config, pool: PgPool) -> Result<()> {
+    let started = Utc::now();
+    let mut sched = JobScheduler::new();
+
+    // 1) Add every job, so a bad expression stops startup before anything runs
+    let mut next_report: Option<(DateTime<Local>, &str)> = None;
+    for report in &cfg.reports {
+        let next = schedule_report(&mut sched, &cfg, &pool, report)?;
+        if next_report.is_none_or(|(earliest, _)| next < earliest) {
+            next_report = Some((next, report.name.as_str()));
+        }
+    }
+    let next_report = match next_report {
+        Some((next, name)) if cfg.reports.len() > 1 => format!("{} ({})", next.format(TS_FMT), name),
+        Some((next, _)) => next.format(TS_FMT).to_string(),
+        None => "not scheduled".to_string(),
+    };
+    schedule_storage_update(&mut sched, &cfg, &pool)?;
+    schedule_prune(&mut sched, &cfg, &pool)?;
+    schedule_missed_backup_check(&mut sched, &cfg, &pool)?;
+
+    // 2) The tick loop runs on its own task for the life of the process
+    sched.start();
+
+    // 3) Close out runs cut short by the previous process
+    let cleanup_pool = pool.clone();
     tokio::spawn(async move {
-        // 1) Preview next run via `cron::Schedule`
-        let schedule = Schedule::from_str(&cfg.email_frequency)
-        .expect("Invalid cron expression in EMAIL_FREQUENCY");
-        
-        // Pull the next run in UTC
-        let next_utc: DateTime<Utc> = schedule
+        match mark_interrupted_runs(&cleanup_pool, started).await {
+            Ok(0) => {}
+            Ok(n) => info!("Marked {} unfinished job run(s) as interrupted", n),
+            Err(e) => error!("Marking interrupted job runs failed: {}", e),
+        }
+    });
+
+    // 4) Announce, catch up on reports missed while down, and take a first
+    //    storage sample right away
+    tokio::spawn(announce_startup(cfg.clone(), next_report));
+    for report in &cfg.reports {
+        tokio::spawn(catch_up_report(cfg.clone(), pool.clone(), report.clone(), started));
+    }
+    tokio::spawn(async move {
+        run_recorded(&pool, &JobKind::StorageUpdate, update_storage(cfg.clone(), pool.clone())).await;
+    });
+    Ok(())
+}
+
+/// Adds `job` to `sched`, running `work` on every tick of `expr` and
+/// recording each run in `job_runs`.
+fn add_job<F, Fut>(sched: &mut JobScheduler, pool: &PgPool, job: JobKind, expr: &str, work: F) -> Result<()>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    let pool = pool.clone();
+    let name = job.name();
+    let setting = job.setting();
+    let cron_job = Job::new_async(expr, move |_uuid, _l| {
+        // The scheduler requires a `Sync` future, which sqlx futures are
+        // not, so run the work on its own task and await the handle
+        let pool = pool.clone();
+        let job = job.clone();
+        let work = work();
+        let task = tokio::spawn(async move { run_recorded(&pool, &job, work).await });
+        Box::pin(async move {
+            if let Err(e) = task.await {
+                error!("Job panicked: {}", e);
+            }
+        })
+    })
+    .map_err(|e| anyhow!("Invalid cron expression in {}: {}", setting, e))?;
+
+    sched
+        .add(cron_job)
+        .map_err(|e| anyhow!("Failed to add {} job: {:?}", name, e))
+}
+
+/// Next run of `expr`, in local time.
+fn next_run(expr: &str, setting: &str) -> Result<DateTime<Local>> {
+    let schedule = Schedule::from_str(expr)
+        .with_context(|| format!("Invalid cron expression in {}", setting))?;
+    let next_utc: DateTime<Utc> = schedule
         .upcoming(Utc)
         .next()
-        .expect("Unable to compute next schedule");
-        
-        // Convert to local zone for display
-        let next_local: DateTime<Local> = next_utc.with_timezone(&Local);
-        
-        ok!(
-            cfg,
-            "System online. Next email report is at {}",
-            next_local.format("%a, %b %e %Y at %I:%M:%S %p %:z")
-        );
-        
-        // 2) Only send a startup email if configured to do so
-        if cfg.send_startup_email {
-            // Attempt to build an EmailClient from your SMTP config
-            match EmailClient::from_config(&cfg) {
-                Ok(client) => {
-                    // Read the startup email HTML template
-                    let mut html = match fs::read_to_string("html/startup_email.html") {
-                        Ok(s) => s,
-                        Err(err) => {
-                            error!("Failed to read HTML template: {}", err);
-                            return;
-                        }
-                    };
-                    
-                    // Replace any placeholders e.g. timestamp, URLs, etc.
-                    html = html.replace("{{HOSTNAME}}", &container_id_from_hostname());
-                    html = html.replace("{{NEXT_REPORT}}", &next_local.format("%a, %b %e %Y at %I:%M:%S %p %:z").to_string());
-                    
-                    html = html.replace("{{TIMESTAMP}}", &format_local_datetime(Local::now()));
-                    html = html.replace(
-                        "{{BACKREST_URL}}",
-                        &cfg.backrest_url.clone().unwrap_or_default(),
-                    );
-                    html = html.replace(
-                        "{{PGADMIN_URL}}",
-                        &cfg.pgadmin_url.clone().unwrap_or_default(),
-                    );
-                    html = html.replace("{{VERSION}}", &cfg.version.to_string());
-
-                    // Build and send the email
-                    if let Err(err) = client.send_html("🎉 Server Startup", html, &cfg).await {
-                        error!("Failed to send startup email: {:?}", err);
-                    } else {
-                        info!(
-                            "Startup email successfully sent to {}",
-                            cfg.email_to.as_deref().unwrap_or("<unset>")
-                        );
-                    }
+        .with_context(|| format!("{} never fires", setting))?;
+    Ok(next_utc.with_timezone(&Local))
+}
+
+/// Schedules `report` on its frequency, after checking its template loads.
+/// Returns when the next one is due.
+fn schedule_report(
+    sched: &mut JobScheduler,
+    cfg: &Config,
+    pool: &PgPool,
+    report: &ReportDefinition,
+) -> Result<DateTime<Local>> {
+    // 1) Check the template and preview the next run
+    let job = JobKind::Report(report.name.clone());
+    templates::check(cfg, &report.content.template).with_context(|| {
+        format!("Template {} of report '{}' failed to load", report.content.template, report.name)
+    })?;
+    let next_local = next_run(&report.frequency, &job.setting())?;
+    if cfg.reports.len() > 1 {
+        info!("Next '{}' report is at {}", report.name, next_local.format(TS_FMT));
+    }
+
+    // 2) Add the job
+    let cfg = cfg.clone();
+    let job_pool = pool.clone();
+    let report = report.clone();
+    let expr = report.frequency.clone();
+    add_job(sched, pool, job, &expr, move || {
+        let cfg = cfg.clone();
+        let pool = job_pool.clone();
+        let report = report.clone();
+        async move {
+            // Failures are already logged and reported
+            send_scheduled_report(&cfg, &pool, &report, Utc::now(), false)
+                .await
+                .map(|_| ())
+                .map_err(|(_, msg)| msg.to_string())
+        }
+    })?;
+
+    Ok(next_local)
+}
+
+/// Finds the fires of `report` missed between the last one sent and `now`,
+/// and handles them per `REPORT_CATCH_UP`. Nothing is sent before the first
+/// scheduled report.
+async fn catch_up_report(cfg: Config, pool: PgPool, report: ReportDefinition, now: DateTime<Utc>) {
+    // 1) End of the last report the scheduler sent
+    let last_end = match last_report_end(&pool, &report.name).await {
+        Ok(Some(t)) => t,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Looking up the last '{}' report failed: {}", report.name, e);
+            return;
+        }
+    };
+
+    // 2) Fires since then, keeping only the latest few
+    let Ok(schedule) = Schedule::from_str(&report.frequency) else {
+        return;
+    };
+    let mut missed = 0usize;
+    let mut latest: VecDeque<DateTime<Utc>> = VecDeque::with_capacity(MAX_CATCH_UP_REPORTS);
+    for fire in schedule.after(&last_end).take_while(|t| *t <= now) {
+        missed += 1;
+        if latest.len() == MAX_CATCH_UP_REPORTS {
+            latest.pop_front();
+        }
+        latest.push_back(fire);
+    }
+    if missed == 0 {
+        return;
+    }
+    tracing::warn!(
+        "Missed {} '{}' report(s) since {} while offline",
+        missed,
+        report.name,
+        last_end.with_timezone(&Local).format(TS_FMT)
+    );
+
+    // 3) Handle them per policy
+    let job = JobKind::Report(report.name.clone());
+    match cfg.report_catch_up {
+        ReportCatchUp::Skip => {
+            info!("REPORT_CATCH_UP is skip, not sending missed '{}' reports", report.name);
+        }
+        ReportCatchUp::Combined => {
+            info!("Sending one catch-up '{}' report for everything since the last one", report.name);
+            let sent = send_report_window(&cfg, &pool, &report, just_after(last_end), now, true);
+            run_recorded(&pool, &job, async {
+                sent.await.map(|_| ()).map_err(|(_, msg)| msg.to_string())
+            })
+            .await;
+        }
+        ReportCatchUp::Each => {
+            if missed > latest.len() {
+                tracing::warn!(
+                    "Only sending the latest {} of {} missed '{}' reports",
+                    latest.len(),
+                    missed,
+                    report.name
+                );
+            }
+            // Aligned windows start after the last report sent, so the first
+            // one also covers any skipped ones
+            for fire in latest {
+                let sent = send_scheduled_report(&cfg, &pool, &report, fire, true);
+                run_recorded(&pool, &job, async {
+                    sent.await.map(|_| ()).map_err(|(_, msg)| msg.to_string())
+                })
+                .await;
+            }
+        }
+    }
+}
+
+/// Reports the server online and sends a startup notice if configured to do so.
+async fn announce_startup(cfg: Config, next_report: String) {
+    ok!(cfg, "System online. Next email report is at {}", next_report);
+
+    if !cfg.send_startup_email {
+        return;
+    }
+
+    // Render the startup email HTML template
+    let html = match templates::render(&cfg, "startup_email.html", context! { next_report }) {
+        Ok(s) => s,
+        Err(err) => {
+            error!("Failed to render startup_email.html: {:#}", err);
+            return;
+        }
+    };
+
+    // Short text version for chat and push channels
+    let mut markdown = format!(
+        "Backrest Summary Reporter {} is online on {}.\nNext report: {}\n",
+        cfg.version,
+        container_id_from_hostname(),
+        next_report
+    );
+    if let Some(url) = cfg.backrest_url.as_deref().filter(|s| !s.is_empty()) {
+        markdown.push_str(&format!("[Open Backrest]({})\n", url));
+    }
+
+    let notification = Notification {
+        title: "🎉 Server Startup".to_string(),
+        html,
+        text: render_startup_text(&cfg, &next_report),
+        markdown,
+        level: NotifyLevel::Info,
+    };
+
+    // Fan out to every configured channel
+    match notify_all(&cfg, &notification).await {
+        Ok(delivered) => info!("Startup notice sent to {} channel(s)", delivered),
+        Err(err) => error!("Failed to send startup notice: {:?}", err),
+    }
+}
+
+/// Schedules storage sampling on `STORAGE_FREQUENCY`.
+fn schedule_storage_update(sched: &mut JobScheduler, cfg: &Config, pool: &PgPool) -> Result<()> {
+    // 1) Preview next run
+    let expr = cfg.storage_frequency.clone();
+    let next_local = next_run(&expr, &JobKind::StorageUpdate.setting())?;
+    info!("Next storage stats update is at {}", next_local.format(TS_FMT));
+
+    // 2) Add the job
+    let cfg = cfg.clone();
+    let job_pool = pool.clone();
+    add_job(sched, pool, JobKind::StorageUpdate, &expr, move || {
+        update_storage(cfg.clone(), job_pool.clone())
+    })
+}
+
+/// Samples every storage mount, then alerts on mounts projected to fill up.
+/// Forecasts run here rather than on every event's storage sample.
+async fn update_storage(cfg: Config, pool: PgPool) -> Result<(), String> {
+    // Failures are already logged and reported
+    let reports = load_and_insert_storage_stats(&pool, &cfg)
+        .await
+        .map_err(|(_, msg)| msg.to_string())?;
+    if !reports.is_empty() {
+        check_storage_forecasts(cfg.clone(), pool.clone()).await;
+    }
+    ok!(cfg, "Storage statistics updated for {} mount(s).", reports.len());
+    Ok(())
+}
+
+/// Schedules pruning of old rows on `PRUNE_FREQUENCY`.
+/// Does nothing if no retention limit is configured.
+fn schedule_prune(sched: &mut JobScheduler, cfg: &Config, pool: &PgPool) -> Result<()> {
+    if !cfg.retention.is_enabled() {
+        info!("No data retention configured, pruning disabled");
+        return Ok(());
+    }
+    let expr = cfg.retention.prune_frequency.clone();
+
+    // 1) Preview next run
+    let next_local = next_run(&expr, &JobKind::Prune.setting())?;
+    info!("Next data retention pruning is at {}", next_local.format(TS_FMT));
+
+    // 2) Add the job
+    let cfg = cfg.clone();
+    let job_pool = pool.clone();
+    add_job(sched, pool, JobKind::Prune, &expr, move || {
+        let cfg = cfg.clone();
+        let pool = job_pool.clone();
+        async move {
+            let now = Local::now().format(TS_FMT).to_string();
+            match prune_old_data(&pool, &cfg).await {
+                Ok(_) => {
+                    info!("Data retention pruning succeeded at {}", now);
+                    Ok(())
                 }
-                Err(err) => {
+                Err((status, msg)) => {
                     error!(
-                        "Unable to construct EmailClient for startup email: {:?}",
-                        err
+                        "Data retention pruning returned {} ({}) at {}",
+                        status,
+                        msg,
+                        now
                     );
+                    Err(msg.to_string())
                 }
             }
         }
-        
-        
-        // 3) Build the actual scheduler
-        let mut sched = JobScheduler::new();
-        
-        // 4) Define and add the job
-        let job = Job::new_async(&cfg.email_frequency, move |_uuid, _l| {
-            // Clone what we need before the async block
-            let api_key  = cfg.auth_key.clone();
-            let http     = http.clone();
-            let port     = cfg.listen_addr.port();
-            let interval = cfg.stats_interval;
-            
-            Box::pin(async move {
-                // Compute the time window
-                let end   = Utc::now();
-                let start = end - ChronoDuration::hours(interval);
-                
-                let body = serde_json::json!({
-                    "start_date": start.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-                    "end_date":   end  .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-                });
-                
-                let url = format!(
-                    "http://localhost:{}/generate-and-send-email-report",
-                    port
-                );
-                
-                match http
-                .post(&url)
-                .header("X-API-Key", api_key)
-                .json(&body)
-                .send()
-                .await
-                {
-                    Ok(_) => info!("Scheduled report triggered at {}", Local::now().to_rfc3339()),
-                    Err(e) => error!(
-                        "Scheduled report POST failed at {}: {}",
-                        Local::now().to_rfc3339(),
-                        e
-                    ),
-                }
-            })
-        })
-        .expect("Invalid cron expression");
-        
-        sched.add(job).expect("Failed to add email cron job");
-        
-        // 5) Start the scheduler loop
-        sched.start().await.expect("Scheduler failed to start");
-    });
+    })
 }
 
-/// Spawns a daily-at-midnight cron job that calls the `/update-storage-statistics` endpoint,
-/// and also triggers one immediate run on startup.
-pub async fn spawn_storage_update_cron(cfg: Config) {
-    // clone for the task
+/// Schedules the check of expected plan/repo schedules for missed runs on
+/// `MISSED_CHECK_FREQUENCY`. Does nothing if no expected schedule is configured.
+fn schedule_missed_backup_check(sched: &mut JobScheduler, cfg: &Config, pool: &PgPool) -> Result<()> {
+    if cfg.missed.expected.is_empty() {
+        info!("No expected schedules configured, missed-backup detection disabled");
+        return Ok(());
+    }
+    let expr = cfg.missed.check_frequency.clone();
+
+    // 1) Preview next run
+    let next_local = next_run(&expr, &JobKind::MissedBackupCheck.setting())?;
+    info!(
+        "Watching {} expected schedule(s), next missed-backup check is at {}",
+        cfg.missed.expected.len(),
+        next_local.format(TS_FMT)
+    );
+
+    // 2) Add the job
     let cfg = cfg.clone();
-    let http = Client::new();
-    
-    tokio::spawn(async move {
-        let port = cfg.listen_addr.port();
-        let url = format!("http://localhost:{}/update-storage-statistics", port);
-        let ts_fmt = "%a, %b %e %Y at %I:%M:%S %p %:z";
-        
-        // Immediate storage stats update
-        {
-            let api_key = cfg.auth_key.clone();
-            let now = Local::now().format(ts_fmt).to_string();
-            
-            match http
-            .get(&url)
-            .header("X-API-Key", api_key)
-            .send()
-            .await
-            {
-                Ok(resp) if resp.status().is_success() => {
-                    info!("Startup storage stats update succeeded at {}", now);
-                }
-                Ok(resp) => error!(
-                    "Startup storage stats update returned {} at {}",
-                    resp.status(),
-                    now
-                ),
-                Err(e) => error!(
-                    "Startup storage stats update failed at {}: {}",
-                    now, e
-                ),
-            }
+    let job_pool = pool.clone();
+    add_job(sched, pool, JobKind::MissedBackupCheck, &expr, move || {
+        let cfg = cfg.clone();
+        let pool = job_pool.clone();
+        async move {
+            check_missed_backups(&cfg, &pool).await;
+            Ok(())
         }
-        
-        // Preview next run
-        let expr = "0 0 0 * * *"; // quartz 6-field: sec=0, min=0, hour=0, daily
-        let schedule = Schedule::from_str(expr)
-        .expect("Invalid cron expression for storage update");
-        
-        // Pull the next run in UTC
-        let next_utc: DateTime<Utc> = schedule
-        .upcoming(Utc)
-        .next()
-        .expect("Unable to compute next schedule");
-        
-        // Convert to local zone for display
-        let next_local: DateTime<Local> = next_utc.with_timezone(&Local);
-        
-        info!(
-            "Next storage stats update is at {}",
-            next_local.format(ts_fmt)
-        );
-        
-        // Build scheduler
-        let mut sched = JobScheduler::new();
-        
-        // Build and add the job
-        let job = Job::new_async(expr, move |_uuid, _l| {
-            // clone inside closure
-            let api_key = cfg.auth_key.clone();
-            let http    = http.clone();
-            let url     = url.clone();
-            
-            Box::pin(async move {
-                let now = Local::now().format(&ts_fmt).to_string();
-                match http
-                .get(&url)
-                .header("X-API-Key", api_key)
-                .send()
-                .await
-                {
-                    Ok(resp) if resp.status().is_success() => {
-                        info!("Storage stats update succeeded at {}", now)
-                    },
-                    Ok(resp) => error!(
-                        "Storage stats update returned {} at {}",
-                        resp.status(),
-                        now
-                    ),
-                    Err(e) => error!(
-                        "Storage stats update POST failed at {}: {}",
-                        now, e
-                    ),
-                }
-            })
-        })
-        .expect("Invalid cron expression for storage update");
-        
-        sched.add(job).expect("Failed to add storage update cron job");
-        
-        // Start the scheduler loop
-        sched.start().await.expect("Scheduler failed to start");
-    });
-}
\ No newline at end of file
+    })
+}
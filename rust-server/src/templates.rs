@@ -0,0 +1,272 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+use minijinja::{context, Environment, Error, ErrorKind, Value};
+use std::{fs, path::Path};
+
+use crate::{
+    charts,
+    config::Config,
+    forecast::format_projection,
+    html_report::{format_bytes, format_change_pct, format_count, format_duration_secs, format_local_offset},
+    utils::{container_id_from_hostname, format_local_datetime},
+};
+
+/// Built-in templates, compiled into the binary so they can't go missing at
+/// runtime. A file with the same name in `TEMPLATE_DIR` takes precedence.
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("report.html",               include_str!("../html/report.html")),
+    ("overdue_table.html",        include_str!("../html/overdue_table.html")),
+    ("repo_breakdown_table.html", include_str!("../html/repo_breakdown_table.html")),
+    ("snapshot_table.html",       include_str!("../html/snapshot_table.html")),
+    ("success_snapshot.html",     include_str!("../html/success_snapshot.html")),
+    ("warn_snapshot.html",        include_str!("../html/warn_snapshot.html")),
+    ("error_snapshot.html",       include_str!("../html/error_snapshot.html")),
+    ("storage_entry.html",        include_str!("../html/storage_entry.html")),
+    ("trends.html",               include_str!("../html/trends.html")),
+    ("alert_email.html",          include_str!("../html/alert_email.html")),
+    ("startup_email.html",        include_str!("../html/startup_email.html")),
+    ("test_email.html",           include_str!("../html/test_email.html")),
+];
+
+/// Renders the named template with `ctx`.
+///
+/// Besides `ctx`, every template can use `server_name`, `backrest_url`,
+/// `pgadmin_url`, `version`, `hostname` and `generated_at`. Values are
+/// HTML-escaped unless marked `| safe`.
+pub fn render(cfg: &Config, name: &str, ctx: Value) -> Result<String, Error> {
+    let env = environment(cfg);
+    let common = context! {
+        server_name  => cfg.server_name.clone().unwrap_or_default(),
+        backrest_url => cfg.backrest_url.clone().unwrap_or_default(),
+        pgadmin_url  => cfg.pgadmin_url.clone().unwrap_or_default(),
+        version      => cfg.version,
+        hostname     => container_id_from_hostname(),
+        generated_at => format_local_datetime(Local::now()),
+    };
+
+    env.get_template(name)?.render(context! { ..ctx, ..common })
+}
+
+/// Checks that the named template exists and compiles.
+pub fn check(cfg: &Config, name: &str) -> Result<(), Error> {
+    environment(cfg).get_template(name).map(|_| ())
+}
+
+/// Builds a fresh environment, so edits in `TEMPLATE_DIR` apply to the next
+/// render without a restart.
+fn environment(cfg: &Config) -> Environment<'static> {
+    let mut env = Environment::new();
+
+    // 1) Overrides first, then the built-in templates
+    let template_dir = cfg.template_dir.clone();
+    env.set_loader(move |name| {
+        if let Some(dir) = &template_dir {
+            let path = Path::new(dir).join(name);
+            if path.is_file() {
+                return fs::read_to_string(&path).map(Some).map_err(|e| {
+                    Error::new(
+                        ErrorKind::TemplateNotFound,
+                        format!("failed to read {}: {}", path.display(), e),
+                    )
+                });
+            }
+        }
+        Ok(BUILTIN_TEMPLATES
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, source)| source.to_string()))
+    });
+
+    // 2) Formatting filters
+    env.add_filter("bytes", bytes);
+    env.add_filter("duration", duration);
+    env.add_filter("datetime", datetime);
+    env.add_filter("utc_offset", utc_offset);
+    env.add_filter("compact", compact);
+    env.add_filter("pct_change", pct_change);
+    env.add_filter("event_cell", event_cell);
+    env.add_filter("outcomes", outcomes);
+    env.add_filter("status_color", status_color);
+    env.add_filter("usage_color", usage_color);
+    env.add_filter("projection", projection);
+
+    // 3) Charts
+    env.add_filter("line_chart", line_chart);
+    env.add_filter("bar_chart", bar_chart);
+
+    env
+}
+
+//
+// FILTERS
+//
+
+/// Placeholder for values that are missing, e.g. no previous period
+const DASH: &str = "–";
+
+/// Numeric value of `v`, or `None` if it is missing or not a number.
+fn number(v: &Value) -> Option<f64> {
+    if v.is_undefined() || v.is_none() {
+        return None;
+    }
+    f64::try_from(v.clone()).ok()
+}
+
+/// Timestamp value of `v`, as serialized by serde (RFC 3339).
+fn timestamp(v: &Value) -> Option<DateTime<FixedOffset>> {
+    v.as_str().and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+}
+
+/// `{{ 1536 | bytes }}` → `1.5 KB`
+fn bytes(v: Value) -> String {
+    number(&v).map_or_else(|| DASH.into(), |b| format_bytes(b.max(0.0) as u64))
+}
+
+/// `{{ 3725 | duration }}` → `01:02:05`
+fn duration(v: Value) -> String {
+    number(&v).map_or_else(|| DASH.into(), |s| format_duration_secs(s as i64))
+}
+
+/// `{{ ts | datetime }}` in the server's local time, optionally with a
+/// strftime format: `{{ ts | datetime("%B %-d, %Y") }}`
+fn datetime(v: Value, format: Option<&str>) -> String {
+    match (timestamp(&v), format) {
+        (Some(dt), Some(f)) => dt.with_timezone(&Local).format(f).to_string(),
+        (Some(dt), None)    => format_local_datetime(dt),
+        (None, _)           => DASH.into(),
+    }
+}
+
+/// `{{ ts | utc_offset }}` → `(UTC -4)`
+fn utc_offset(v: Value) -> String {
+    timestamp(&v).map_or_else(String::new, format_local_offset)
+}
+
+/// `{{ 123456 | compact }}` → `1.2e5`; counts over 9,999 use scientific notation.
+fn compact(v: Value) -> String {
+    number(&v).map_or_else(|| DASH.into(), |n| format_count(n as i64))
+}
+
+/// `{{ current | pct_change(previous) }}` → `↑12.50%` or `↓3.00%`.
+/// A dash when there is nothing to compare with, or either side is negative.
+fn pct_change(current: Value, previous: Value) -> String {
+    match number(&current) {
+        Some(cur) => format_change_pct(cur, number(&previous)),
+        None => DASH.into(),
+    }
+}
+
+/// `{{ n | event_cell("✅") }}` → the emoji floated left of the count, or a
+/// dash when zero.
+fn event_cell(n: Value, emoji: &str) -> Value {
+    match number(&n) {
+        Some(n) if n != 0.0 => Value::from_safe_string(format!(
+            "<span style=\"float: left;\">{}</span> {}",
+            emoji, n as i64
+        )),
+        _ => Value::from(DASH),
+    }
+}
+
+/// `{{ totals | outcomes("error") }}` → number of snapshot, forget, prune and
+/// check events with that outcome (`success`, `warning` or `error`).
+fn outcomes(totals: Value, outcome: &str) -> Result<i64, Error> {
+    if !matches!(outcome, "success" | "warning" | "error") {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("unknown outcome '{}'", outcome),
+        ));
+    }
+    Ok(["snapshot", "forget", "prune", "check"]
+        .iter()
+        .filter_map(|op| {
+            let field = totals.get_attr(&format!("total_{}_{}", op, outcome)).ok()?;
+            number(&field)
+        })
+        .sum::<f64>() as i64)
+}
+
+/// `{{ totals | status_color }}` → red with errors, yellow with warnings,
+/// transparent otherwise (or when there are no totals).
+fn status_color(totals: Value) -> Result<&'static str, Error> {
+    if totals.is_undefined() || totals.is_none() {
+        return Ok("#d1ecf100");
+    }
+    Ok(if outcomes(totals.clone(), "error")? > 0 {
+        "#f8d7da"   // error red
+    } else if outcomes(totals, "warning")? > 0 {
+        "#fff3cd"   // warning yellow
+    } else {
+        "#d1ecf100" // transparent blue (info)
+    })
+}
+
+/// `{{ stat.usage_level | usage_color }}` → red when critical, yellow at
+/// warning, green otherwise.
+fn usage_color(level: &str) -> &'static str {
+    match level {
+        "critical" => "#b02020",
+        "warning"  => "#e0c020",
+        _          => "#80c080",
+    }
+}
+
+/// `{{ f.days_until_full | projection(f.full_at) }}` → `in 12 days (Oct 29, 2026)`,
+/// `now` once reached, or `never` when not growing.
+fn projection(days: Value, at: Value) -> String {
+    format_projection(number(&days), timestamp(&at).map(|t| t.to_utc()))
+}
+
+/// `{{ trend.days | line_chart("used_bytes", "bytes") }}` → inline SVG line
+/// chart of that field per day. Units are `bytes`, `duration` or `count`.
+fn line_chart(days: Value, field: &str, unit: &str, color: Option<&str>) -> Result<Value, Error> {
+    let points = chart_points(&days, field)?;
+    Ok(Value::from_safe_string(charts::line_chart(
+        &points,
+        chart_unit(unit)?,
+        color.unwrap_or("#3070b0"),
+    )))
+}
+
+/// `{{ trends.daily | bar_chart("data_added", "bytes") }}` → inline SVG bar
+/// chart of that field per day. Units are `bytes`, `duration` or `count`.
+fn bar_chart(days: Value, field: &str, unit: &str, color: Option<&str>) -> Result<Value, Error> {
+    let points = chart_points(&days, field)?;
+    Ok(Value::from_safe_string(charts::bar_chart(
+        &points,
+        chart_unit(unit)?,
+        color.unwrap_or("#3070b0"),
+    )))
+}
+
+/// `(day, value)` pairs from a list of objects with a `day` and `field`.
+fn chart_points(days: &Value, field: &str) -> Result<Vec<(NaiveDate, f64)>, Error> {
+    if days.is_undefined() || days.is_none() {
+        return Ok(Vec::new());
+    }
+    let mut points = Vec::new();
+    for entry in days.try_iter()? {
+        let day = entry
+            .get_attr("day")?
+            .as_str()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidOperation, "chart entry without a valid 'day'"))?;
+        let value = number(&entry.get_attr(field)?).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidOperation, format!("chart entry without a numeric '{}'", field))
+        })?;
+        points.push((day, value));
+    }
+    Ok(points)
+}
+
+/// Label formatter for a chart unit.
+fn chart_unit(unit: &str) -> Result<fn(f64) -> String, Error> {
+    match unit {
+        "bytes"    => Ok(|v| format_bytes(v.max(0.0) as u64)),
+        "duration" => Ok(|v| format_duration_secs(v as i64)),
+        "count"    => Ok(|v| format_count(v as i64)),
+        _ => Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("unknown chart unit '{}'", unit),
+        )),
+    }
+}
@@ -0,0 +1,352 @@
+use chrono::Local;
+
+use crate::{
+    config::Config,
+    forecast::format_projection,
+    html_report::{format_bytes, format_change_pct, format_count, format_duration_secs, format_local_offset},
+    models::{EventTotals, GenerateReport},
+    utils::{container_id_from_hostname, format_local_datetime},
+};
+
+/// Plain-text version of the report, sent as the alternative part of the
+/// report email.
+pub fn render_report_text(cfg: &Config, report: &GenerateReport) -> String {
+    let et = &report.event_totals.current;
+    // Only the comparison periods the report asks for get a column
+    let shown = [report.compare.day, report.compare.week, report.compare.month];
+    let periods: Vec<_> = [
+        &report.event_totals.previous_day,
+        &report.event_totals.previous_week,
+        &report.event_totals.previous_month,
+    ]
+    .into_iter()
+    .zip(shown)
+    .filter_map(|(prev, on)| on.then_some(prev))
+    .collect();
+    let period_labels: Vec<&str> = ["Day", "Week", "Month"]
+        .into_iter()
+        .zip(shown)
+        .filter_map(|(label, on)| on.then_some(label))
+        .collect();
+    let mut out = String::new();
+
+    // 1) Heading and report window
+    heading(&mut out, '=', &format!(
+        "{}Backup Summary for {}",
+        report.name.as_deref().map(|n| format!("{} ", n)).unwrap_or_default(),
+        cfg.server_name.as_deref().filter(|s| !s.is_empty()).unwrap_or("Backrest")
+    ));
+    out.push_str(&format!(
+        "{} – {} {}\n",
+        et.start_date.with_timezone(&Local).format("%B %-d, %Y, %-I:%M %p"),
+        et.end_date.with_timezone(&Local).format("%B %-d, %Y, %-I:%M %p"),
+        format_local_offset(et.start_date)
+    ));
+
+    // 2) Plans or repos that missed a run
+    if !report.overdue_schedules.is_empty() {
+        heading(&mut out, '-', "Overdue Backups");
+        let rows = report.overdue_schedules.iter().map(|o| vec![
+            format!("{} {}", if o.scope == "repo" { "Repo" } else { "Plan" }, o.name),
+            o.cadence.clone(),
+            o.last_seen.map(format_local_datetime).unwrap_or_else(|| "Never".into()),
+            o.due_by.map(format_local_datetime).unwrap_or_else(|| "–".into()),
+        ]);
+        out.push_str(&text_table(&["Plan / Repo", "Expected", "Last Event", "Due By"], 4, rows));
+    }
+
+    // 3) Event counts
+    heading(&mut out, '-', "Events");
+    let rows = [
+        ("Snapshots", et.total_snapshot_success, et.total_snapshot_warning, et.total_snapshot_error),
+        ("Forgets",   et.total_forget_success,   et.total_forget_warning,   et.total_forget_error),
+        ("Prunes",    et.total_prune_success,    et.total_prune_warning,    et.total_prune_error),
+        ("Checks",    et.total_check_success,    et.total_check_warning,    et.total_check_error),
+    ]
+    .into_iter()
+    .map(|(label, success, warning, error)| vec![
+        label.to_string(),
+        success.to_string(),
+        warning.to_string(),
+        error.to_string(),
+    ]);
+    out.push_str(&text_table(&["Event Type", "Success", "Warnings", "Errors"], 1, rows));
+
+    // 4) Totals against the previous day, week and month
+    heading(&mut out, '-', "Totals");
+    let mut rows = Vec::new();
+    let metrics: [(&str, Extractor, Formatter); 3] = [
+        ("Duration",       |e| e.total_duration,        format_duration_secs),
+        ("Data added",     |e| e.total_data_added,      |v| format_bytes(v.max(0) as u64)),
+        ("Data processed", |e| e.total_bytes_processed, |v| format_bytes(v.max(0) as u64)),
+    ];
+    for (label, extract, format) in metrics {
+        let cur = extract(et);
+        let mut row = vec![label.to_string(), format(cur)];
+        for prev in &periods {
+            row.push(match prev {
+                Some(p) => format!(
+                    "{} ({})",
+                    format(extract(p)),
+                    format_change_pct(cur as f64, Some(extract(p) as f64))
+                ),
+                None => "–".into(),
+            });
+        }
+        rows.push(row);
+    }
+    let counts: [(&str, Extractor); 6] = [
+        ("New files",        |e| e.total_files_new),
+        ("Changed files",    |e| e.total_files_changed),
+        ("Unmodified files", |e| e.total_files_unmodified),
+        ("New dirs",         |e| e.total_dirs_new),
+        ("Changed dirs",     |e| e.total_dirs_changed),
+        ("Unmodified dirs",  |e| e.total_dirs_unmodified),
+    ];
+    for (label, extract) in counts {
+        let mut row = vec![label.to_string(), format_count(extract(et))];
+        for prev in &periods {
+            row.push(prev.as_ref().map_or_else(|| "–".into(), |p| format_count(extract(p))));
+        }
+        rows.push(row);
+    }
+    let headers: Vec<&str> = ["", "Total"].into_iter().chain(period_labels.iter().copied()).collect();
+    out.push_str(&text_table(&headers, 1, rows));
+
+    // 5) Per-repo breakdown
+    if !report.totals_breakdown.by_repo.is_empty() {
+        heading(&mut out, '-', "Repositories");
+        let rows = report.totals_breakdown.by_repo.iter().map(|group| {
+            let g = &group.current;
+            vec![
+                group.name.clone(),
+                outcomes(g, Outcome::Success).to_string(),
+                outcomes(g, Outcome::Warning).to_string(),
+                outcomes(g, Outcome::Error).to_string(),
+                format_bytes(g.total_data_added.max(0) as u64),
+                format_duration_secs(g.total_duration),
+                format_count(g.total_files_new),
+                format_count(g.total_files_changed),
+            ]
+        });
+        out.push_str(&text_table(
+            &["Repo", "Success", "Warnings", "Errors", "Data Added", "Duration", "Files New", "Files Changed"],
+            1,
+            rows,
+        ));
+    }
+
+    // 6) Snapshots, with the messages of any that failed
+    let snapshots: Vec<_> = report
+        .snapshot_summaries
+        .iter()
+        .filter(|s| matches!(s.event.as_str(), "snapshot success" | "snapshot warning" | "snapshot error"))
+        .collect();
+    if !snapshots.is_empty() {
+        heading(&mut out, '-', "Snapshots");
+        let rows = snapshots.iter().map(|s| vec![
+            s.plan.clone(),
+            s.event.trim_start_matches("snapshot ").to_uppercase(),
+            if s.snapshot.is_empty() || s.snapshot == "null" {
+                "N/A".into()
+            } else {
+                s.snapshot.chars().take(10).collect()
+            },
+            format_local_datetime(s.created_at),
+            s.data_added.map_or_else(|| "–".into(), |v| format_bytes(v.max(0) as u64)),
+            s.total_duration.map_or_else(|| "–".into(), |v| format_duration_secs(v as i64)),
+        ]);
+        out.push_str(&text_table(&["Plan", "Status", "Snapshot", "Time", "Data Added", "Duration"], 4, rows));
+
+        let failed: Vec<_> = snapshots.iter().filter(|s| s.event != "snapshot success").collect();
+        if !failed.is_empty() {
+            out.push('\n');
+        }
+        for s in failed {
+            out.push_str(&format!(
+                "- {} ({}): {}\n",
+                s.plan,
+                format_local_datetime(s.created_at),
+                s.error.as_deref().unwrap_or("N/A")
+            ));
+        }
+    }
+
+    // 7) Storage usage against the previous day, week and month
+    if !report.storage_statistics.is_empty() {
+        heading(&mut out, '-', "Storage");
+        let rows = report.storage_statistics.iter().map(|stat| {
+            let cur = stat.current.percent_used;
+            let mut row = vec![
+                stat.nickname.clone().filter(|s| !s.is_empty()).unwrap_or_else(|| stat.location.clone()),
+                format_bytes(stat.current.used_bytes.max(0) as u64),
+                format_bytes(stat.current.total_bytes.max(0) as u64),
+                format!("{:.2}%", cur),
+            ];
+            let changes = [&stat.previous_day, &stat.previous_week, &stat.previous_month]
+                .into_iter()
+                .zip(shown)
+                .filter(|(_, on)| *on)
+                .map(|(prev, _)| format_change_pct(cur, prev.as_ref().map(|p| p.percent_used)));
+            row.extend(changes);
+            row.push(
+                stat.forecast
+                    .as_ref()
+                    .map_or_else(|| "–".into(), |f| format_projection(f.days_until_full, f.full_at)),
+            );
+            row
+        });
+        let headers: Vec<&str> = ["Storage", "Used", "Total", "% Used"]
+            .into_iter()
+            .chain(period_labels.iter().copied())
+            .chain(["Full"])
+            .collect();
+        out.push_str(&text_table(&headers, 1, rows));
+    }
+
+    footer(cfg, &mut out);
+    out
+}
+
+/// Plain-text version of an alert (see `alerts::render_alert`).
+pub fn render_alert_text(
+    cfg: &Config,
+    title: &str,
+    details: &[(&str, String)],
+    message: Option<&str>,
+    note: &str,
+) -> String {
+    let mut out = String::new();
+    heading(&mut out, '=', title);
+
+    let server = ("Server", container_id_from_hostname());
+    let width = details.iter().chain([&server]).map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+    for (label, value) in [&server].into_iter().chain(details) {
+        let line = format!("{:<width$}  {}", format!("{}:", label), value, width = width + 1);
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    if let Some(msg) = message {
+        out.push_str(&format!("\n{}\n", msg));
+    }
+    if !note.is_empty() {
+        out.push_str(&format!("\n{}\n", note));
+    }
+
+    footer(cfg, &mut out);
+    out
+}
+
+/// Plain-text version of the startup notice.
+pub fn render_startup_text(cfg: &Config, next_report: &str) -> String {
+    let mut out = String::new();
+    heading(&mut out, '=', &format!("Backrest Summary Reporter Online ({})", container_id_from_hostname()));
+    out.push_str(&format!(
+        "All systems are online. The next summary report will be at {}.\n",
+        next_report
+    ));
+    footer(cfg, &mut out);
+    out
+}
+
+/// Plain-text version of the SMTP test email.
+pub fn render_test_text(cfg: &Config) -> String {
+    let mut out = String::new();
+    heading(&mut out, '=', "SMTP Configuration Test");
+    out.push_str("If you can read this email, your SMTP setup is working correctly.\n");
+    footer(cfg, &mut out);
+    out
+}
+
+//
+// HELPERS
+//
+
+/// Picks one total out of `EventTotals`
+type Extractor = fn(&EventTotals) -> i64;
+/// Formats a total for display
+type Formatter = fn(i64) -> String;
+
+#[derive(Clone, Copy)]
+enum Outcome {
+    Success,
+    Warning,
+    Error,
+}
+
+/// Number of snapshot, forget, prune and check events with the given outcome.
+fn outcomes(et: &EventTotals, outcome: Outcome) -> i64 {
+    match outcome {
+        Outcome::Success => et.total_snapshot_success + et.total_forget_success + et.total_prune_success + et.total_check_success,
+        Outcome::Warning => et.total_snapshot_warning + et.total_forget_warning + et.total_prune_warning + et.total_check_warning,
+        Outcome::Error   => et.total_snapshot_error   + et.total_forget_error   + et.total_prune_error   + et.total_check_error,
+    }
+}
+
+/// Appends `title` underlined with `underline`, preceded by a blank line
+/// unless it starts the text.
+fn heading(out: &mut String, underline: char, title: &str) {
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(title);
+    out.push('\n');
+    out.push_str(&underline.to_string().repeat(title.chars().count()));
+    out.push('\n');
+}
+
+/// Links, generation time and version, shared by every plain-text email.
+fn footer(cfg: &Config, out: &mut String) {
+    out.push_str("\n--\n");
+    if let Some(url) = cfg.backrest_url.as_deref().filter(|s| !s.is_empty()) {
+        out.push_str(&format!("Backrest: {}\n", url));
+    }
+    if let Some(url) = cfg.pgadmin_url.as_deref().filter(|s| !s.is_empty()) {
+        out.push_str(&format!("pgAdmin:  {}\n", url));
+    }
+    out.push_str(&format!(
+        "Generated on {} · Version {}\n",
+        format_local_datetime(Local::now()),
+        cfg.version
+    ));
+}
+
+/// Lays out rows as a table with a header rule. The first `left` columns are
+/// left-aligned and the rest right-aligned, each padded to its widest cell.
+fn text_table<I>(headers: &[&str], left: usize, rows: I) -> String
+where
+    I: IntoIterator<Item = Vec<String>>,
+{
+    let rows: Vec<Vec<String>> = rows.into_iter().collect();
+
+    // 1) Widest cell per column, by characters so arrows and dashes count as one
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    // 2) Pad every cell and join with two spaces
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (cell, &width))| {
+                let pad = " ".repeat(width.saturating_sub(cell.chars().count()));
+                if i < left { format!("{}{}", cell, pad) } else { format!("{}{}", pad, cell) }
+            })
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+
+    let header: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+    let mut out = line(&header);
+    out.push_str(&line(&rule));
+    for row in &rows {
+        out.push_str(&line(row));
+    }
+    out
+}
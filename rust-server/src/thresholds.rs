@@ -0,0 +1,250 @@
+use sqlx::{PgPool, Row};
+
+use crate::{
+    warn,
+    alerts::render_alert,
+    config::{Config, StorageConfig},
+    html_report::format_bytes,
+    notify::{notify_all, NotifyLevel},
+};
+
+/// How full a mount is against its warning and critical thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UsageLevel {
+    Ok,
+    Warning,
+    Critical,
+}
+
+impl UsageLevel {
+    /// Stable lowercase name, as stored and serialized
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UsageLevel::Ok       => "ok",
+            UsageLevel::Warning  => "warning",
+            UsageLevel::Critical => "critical",
+        }
+    }
+
+    fn from_db(s: &str) -> Self {
+        match s {
+            "critical" => UsageLevel::Critical,
+            "warning"  => UsageLevel::Warning,
+            _          => UsageLevel::Ok,
+        }
+    }
+}
+
+/// Level of a mount using `used_bytes` of `total_bytes`.
+pub fn usage_level(mount: &StorageConfig, used_bytes: u64, total_bytes: u64) -> UsageLevel {
+    level_with_margin(mount, used_bytes, total_bytes, 0.0)
+}
+
+/// Level with both thresholds lowered by `margin` bytes.
+fn level_with_margin(mount: &StorageConfig, used_bytes: u64, total_bytes: u64, margin: f64) -> UsageLevel {
+    let used = used_bytes as f64;
+    if used >= mount.crit.used_limit(total_bytes) - margin {
+        UsageLevel::Critical
+    } else if used >= mount.warn.used_limit(total_bytes) - margin {
+        UsageLevel::Warning
+    } else {
+        UsageLevel::Ok
+    }
+}
+
+/// Level after a new sample, coming from `previous`. A higher level applies
+/// right away; a lower one only once usage is `STORAGE_THRESHOLD_HYSTERESIS`
+/// percent of capacity below the threshold.
+fn next_level(
+    hysteresis: f64,
+    mount: &StorageConfig,
+    previous: UsageLevel,
+    used_bytes: u64,
+    total_bytes: u64,
+) -> UsageLevel {
+    let level = usage_level(mount, used_bytes, total_bytes);
+    if level >= previous {
+        return level;
+    }
+    let margin = total_bytes as f64 * hysteresis / 100.0;
+    previous.min(level_with_margin(mount, used_bytes, total_bytes, margin))
+}
+
+/// Records the mount's usage level after a new sample and alerts every
+/// channel when it changes. Reaching critical also fails the healthcheck.
+/// Failures are logged only.
+pub async fn check_storage_thresholds(
+    cfg: Config,
+    pool: PgPool,
+    mount: StorageConfig,
+    used_bytes: u64,
+    total_bytes: u64,
+) {
+    // 1) Level as of the previous sample, starting at ok
+    let previous = match previous_level(&pool, &mount.path).await {
+        Ok(l) => l,
+        Err(e) => {
+            tracing::error!("Usage level lookup for {} failed: {}", mount.path, e);
+            return;
+        }
+    };
+
+    // 2) Nothing to do unless it changed
+    let level = next_level(cfg.storage_hysteresis, &mount, previous, used_bytes, total_bytes);
+    if level == previous {
+        return;
+    }
+
+    // 3) Record it, unless a concurrent sample already did
+    let updated = sqlx::query(
+        r#"
+        UPDATE storage_usage_state
+           SET level = $3, changed_at = now()
+         WHERE storage_location = $1
+           AND level = $2
+        "#,
+    )
+    .bind(&mount.path)
+    .bind(previous.as_str())
+    .bind(level.as_str())
+    .execute(&pool)
+    .await;
+    match updated {
+        Ok(r) if r.rows_affected() == 1 => {}
+        Ok(_) => return,
+        Err(e) => {
+            tracing::error!("Usage level update for {} failed: {}", mount.path, e);
+            return;
+        }
+    }
+
+    // 4) Alert
+    let name = mount.nickname.as_deref().unwrap_or(&mount.path);
+    let pct = if total_bytes > 0 { used_bytes as f64 / total_bytes as f64 * 100.0 } else { 0.0 };
+    if level == UsageLevel::Critical {
+        warn!(
+            cfg.healthcheck_url,
+            "Storage critical",
+            "{} is {:.1}% used ({} free), past its critical threshold of {}",
+            name,
+            pct,
+            format_bytes(total_bytes.saturating_sub(used_bytes)),
+            mount.crit
+        );
+    }
+
+    let (title, notify_level) = match (level, level > previous) {
+        (UsageLevel::Critical, _)    => (format!("🔴 Storage critical: {}", name), NotifyLevel::Error),
+        (UsageLevel::Warning, true)  => (format!("🟡 Storage warning: {}", name), NotifyLevel::Warning),
+        (UsageLevel::Warning, false) => (format!("🟡 Storage below critical: {}", name), NotifyLevel::Warning),
+        (UsageLevel::Ok, _)          => (format!("🟢 Storage back to normal: {}", name), NotifyLevel::Success),
+    };
+    let notification = render_alert(
+        &cfg,
+        title,
+        notify_level,
+        &[
+            ("Mount",    mount.path.clone()),
+            ("Used",     format!(
+                "{} of {} ({:.1}%)",
+                format_bytes(used_bytes),
+                format_bytes(total_bytes),
+                pct
+            )),
+            ("Free",     format_bytes(total_bytes.saturating_sub(used_bytes))),
+            ("Warning",  mount.warn.to_string()),
+            ("Critical", mount.crit.to_string()),
+            ("Previous", previous.as_str().to_string()),
+        ],
+        None,
+        "",
+    );
+    match notify_all(&cfg, &notification).await {
+        Ok(delivered) => tracing::info!(
+            "Alert '{}' sent to {} channel(s)",
+            notification.title,
+            delivered
+        ),
+        Err((_, msg)) => tracing::error!("Alert '{}' failed: {}", notification.title, msg),
+    }
+}
+
+/// Level the mount was last recorded at, creating it as ok if new.
+async fn previous_level(pool: &PgPool, location: &str) -> Result<UsageLevel, sqlx::Error> {
+    let row = sqlx::query(
+        r#"
+        WITH created AS (
+            INSERT INTO storage_usage_state (storage_location, level)
+            VALUES ($1, 'ok')
+            ON CONFLICT (storage_location) DO NOTHING
+            RETURNING level
+        )
+        SELECT level FROM created
+        UNION ALL
+        SELECT level FROM storage_usage_state WHERE storage_location = $1
+        LIMIT 1
+        "#,
+    )
+    .bind(location)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(UsageLevel::from_db(row.get("level")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Threshold;
+
+    const TOTAL: u64 = 1000;
+    const HYSTERESIS: f64 = 5.0;
+
+    /// Warning at 80%, critical at 90%
+    fn mount() -> StorageConfig {
+        StorageConfig {
+            path:     "/backups".into(),
+            nickname: None,
+            warn:     Threshold::PercentUsed(80.0),
+            crit:     Threshold::PercentUsed(90.0),
+        }
+    }
+
+    fn next(previous: UsageLevel, used: u64) -> UsageLevel {
+        next_level(HYSTERESIS, &mount(), previous, used, TOTAL)
+    }
+
+    #[test]
+    fn levels_with_margin() {
+        let m = mount();
+        assert_eq!(level_with_margin(&m, 799, TOTAL, 0.0), UsageLevel::Ok);
+        assert_eq!(level_with_margin(&m, 800, TOTAL, 0.0), UsageLevel::Warning);
+        assert_eq!(level_with_margin(&m, 900, TOTAL, 0.0), UsageLevel::Critical);
+        assert_eq!(level_with_margin(&m, 850, TOTAL, 50.0), UsageLevel::Critical);
+        assert_eq!(level_with_margin(&m, 749, TOTAL, 50.0), UsageLevel::Ok);
+    }
+
+    #[test]
+    fn escalates_right_away() {
+        assert_eq!(next(UsageLevel::Ok, 800), UsageLevel::Warning);
+        assert_eq!(next(UsageLevel::Warning, 900), UsageLevel::Critical);
+        assert_eq!(next(UsageLevel::Ok, 950), UsageLevel::Critical);
+    }
+
+    #[test]
+    fn stays_critical_just_under_threshold() {
+        assert_eq!(next(UsageLevel::Critical, 899), UsageLevel::Critical);
+        assert_eq!(next(UsageLevel::Critical, 850), UsageLevel::Critical);
+    }
+
+    #[test]
+    fn clears_only_past_hysteresis() {
+        // Critical clears to warning more than 5% below 90%
+        assert_eq!(next(UsageLevel::Critical, 849), UsageLevel::Warning);
+        // Warning holds down to 5% below 80%, then clears
+        assert_eq!(next(UsageLevel::Warning, 750), UsageLevel::Warning);
+        assert_eq!(next(UsageLevel::Warning, 749), UsageLevel::Ok);
+        // A large drop clears critical straight to ok
+        assert_eq!(next(UsageLevel::Critical, 500), UsageLevel::Ok);
+    }
+}
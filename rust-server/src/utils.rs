@@ -1,5 +1,4 @@
-use axum::http::StatusCode;
-use chrono::{DateTime, Local, Offset, TimeZone};
+use chrono::{DateTime, Local, TimeZone};
 use std::fs;
 
 /// Log a failure, ping healthcheck(fail) with a combined message of
@@ -8,11 +7,11 @@ use std::fs;
 ///
 /// # Parameters
 /// - `cfg`
-///     Your application `Config`, so we can grab `cfg.healthcheck_url`.
+///   Your application `Config`, so we can grab `cfg.healthcheck_url`.
 /// - `static_msg`
-///     A **`&'static str`** that will be returned to the HTTP caller.
+///   A **`&'static str`** that will be returned to the HTTP caller.
 /// - `format_args...`
-///     A `format!`-style string plus any arguments to build the **detailed** part.
+///   A `format!`-style string plus any arguments to build the **detailed** part.
 ///
 /// # Usage
 /// ```ignore
@@ -34,9 +33,9 @@ macro_rules! fail {
         // combine with the static prefix
         let combined = format!("{}: {}", $static_msg, detail);
         tracing::error!("{}", combined);
-        crate::healthcheck::ping_healthcheck(
+        $crate::healthcheck::ping_healthcheck(
             &$cfg.healthcheck_url,
-            crate::healthcheck::HealthStatus::Fail,
+            $crate::healthcheck::HealthStatus::Fail,
             Some(&combined),
         );
         // return only the static part for the HTTP response
@@ -49,11 +48,11 @@ macro_rules! fail {
 ///
 /// # Parameters
 /// - `healthcheck_url`
-///     The healthcheck URL to ping.
+///   The healthcheck URL to ping.
 /// - `static_msg`
-///     A **`&'static str`** that will be returned to the HTTP caller.
+///   A **`&'static str`** that will be returned to the HTTP caller.
 /// - `format_args...`
-///     A `format!`-style string plus any arguments to build the **detailed** part.
+///   A `format!`-style string plus any arguments to build the **detailed** part.
 ///
 /// # Usage
 /// ```ignore
@@ -75,9 +74,9 @@ macro_rules! warn {
         // combine with the static prefix
         let combined = format!("{}: {}", $static_msg, detail);
         tracing::error!("{}", combined);
-        crate::healthcheck::ping_healthcheck(
+        $crate::healthcheck::ping_healthcheck(
             &$healthcheck_url,
-            crate::healthcheck::HealthStatus::Fail,
+            $crate::healthcheck::HealthStatus::Fail,
             Some(&combined),
         );
     }};
@@ -88,9 +87,9 @@ macro_rules! warn {
 ///
 /// # Parameters
 /// - `cfg`
-///     Your application `Config`.
+///   Your application `Config`.
 /// - `format_args...`
-///     A `format!`-style string plus any arguments to build the message.
+///   A `format!`-style string plus any arguments to build the message.
 ///
 /// # Usage
 /// ```ignore
@@ -104,9 +103,9 @@ macro_rules! ok {
         // combine with a fixed "Success" prefix
         let combined = format!("Success: {}", detail);
         tracing::info!("{}", combined);
-        crate::healthcheck::ping_healthcheck(
+        $crate::healthcheck::ping_healthcheck(
             &$cfg.healthcheck_url,
-            crate::healthcheck::HealthStatus::Success,
+            $crate::healthcheck::HealthStatus::Success,
             Some(&combined),
         );
     }};
@@ -117,9 +116,9 @@ macro_rules! ok {
 ///
 /// # Parameters
 /// - `cfg`
-///     Your application `Config`.
+///   Your application `Config`.
 /// - `format_args...`
-///     A `format!`-style string plus any arguments to build the message.
+///   A `format!`-style string plus any arguments to build the message.
 ///
 /// # Usage
 /// ```ignore
@@ -133,9 +132,9 @@ macro_rules! start {
         // combine with a fixed "Start" prefix
         let combined = format!("Start: {}", detail);
         tracing::info!("{}", combined);
-        crate::healthcheck::ping_healthcheck(
+        $crate::healthcheck::ping_healthcheck(
             &$cfg.healthcheck_url,
-            crate::healthcheck::HealthStatus::Start,
+            $crate::healthcheck::HealthStatus::Start,
             Some(&combined),
         );
     }};